@@ -0,0 +1,190 @@
+use std::time::Instant;
+
+use cgmath::Vector2;
+
+use secs;
+
+/// A screen rectangle in pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Region {
+    /// Parses `x,y,width,height`
+    pub fn parse(s: &str) -> Option<Region> {
+        let nums: Vec<i32> = match s.split(',').map(|n| n.trim().parse()).collect() {
+            Ok(nums) => nums,
+            Err(_) => return None,
+        };
+        if nums.len() != 4 || nums[2] <= 0 || nums[3] <= 0 {
+            return None;
+        }
+        Some(Region {
+                 x: nums[0],
+                 y: nums[1],
+                 width: nums[2],
+                 height: nums[3],
+             })
+    }
+
+    pub fn contains(&self, pt: Vector2<i32>) -> bool {
+        pt.x >= self.x && pt.x < self.x + self.width && pt.y >= self.y &&
+        pt.y < self.y + self.height
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ClickConfirmParams {
+    /// clicks in these regions need a second trigger
+    pub regions: Vec<Region>,
+    pub confirm_time: f32, // seconds the second trigger has to come within
+    pub max_drift: i32, // pixels the cursor may move between the two triggers
+}
+
+/// Protects users with unreliable triggers from costly misclicks by requiring
+/// a second trigger for clicks over dangerous parts of the screen, like a
+/// "Send" or "Delete" button.
+pub struct ClickGuard {
+    params: ClickConfirmParams,
+    pending: Option<(Vector2<i32>, Instant)>,
+}
+
+impl ClickGuard {
+    pub fn new(params: ClickConfirmParams) -> Self {
+        ClickGuard {
+            params,
+            pending: None,
+        }
+    }
+
+    /// Where a click is waiting for confirmation, if one is
+    pub fn pending(&self, now: Instant) -> Option<Vector2<i32>> {
+        match self.pending {
+            Some((pt, time)) if self.in_time(time, now) => Some(pt),
+            _ => None,
+        }
+    }
+
+    /// Returns whether a trigger at `cursor` should click now
+    pub fn trigger(&mut self, cursor: Vector2<i32>, now: Instant) -> bool {
+        if let Some((pt, time)) = self.pending.take() {
+            let drift = (cursor.x - pt.x).abs().max((cursor.y - pt.y).abs());
+            if self.in_time(time, now) && drift <= self.params.max_drift {
+                return true;
+            }
+        }
+
+        if self.params.regions.iter().any(|region| region.contains(cursor)) {
+            println!("Click at ({}, {}) needs confirmation, trigger again to click",
+                     cursor.x,
+                     cursor.y);
+            self.pending = Some((cursor, now));
+            return false;
+        }
+        true
+    }
+
+    fn in_time(&self, time: Instant, now: Instant) -> bool {
+        secs(now.duration_since(time)) <= self.params.confirm_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use cgmath::vec2;
+
+    fn guard() -> ClickGuard {
+        ClickGuard::new(ClickConfirmParams {
+            regions: vec![Region {
+                              x: 100,
+                              y: 100,
+                              width: 50,
+                              height: 20,
+                          }],
+            confirm_time: 2.0,
+            max_drift: 20,
+        })
+    }
+
+    #[test]
+    fn parses_regions() {
+        assert_eq!(Region::parse("10, 20,300,40"),
+                   Some(Region {
+                            x: 10,
+                            y: 20,
+                            width: 300,
+                            height: 40,
+                        }));
+        assert_eq!(Region::parse("-10,-20,1,1").map(|r| (r.x, r.y)), Some((-10, -20)));
+        assert_eq!(Region::parse("10,20,300"), None);
+        assert_eq!(Region::parse("10,20,300,40,5"), None);
+        assert_eq!(Region::parse("10,20,x,300,40"), None);
+        assert_eq!(Region::parse("10,20,0,40"), None);
+        assert_eq!(Region::parse("10,20,300,-40"), None);
+        assert_eq!(Region::parse(""), None);
+    }
+
+    #[test]
+    fn region_excludes_far_edges() {
+        let region = Region::parse("100,100,50,20").unwrap();
+        assert!(region.contains(vec2(100, 100)));
+        assert!(region.contains(vec2(149, 119)));
+        assert!(!region.contains(vec2(150, 110)));
+        assert!(!region.contains(vec2(120, 120)));
+        assert!(!region.contains(vec2(99, 110)));
+    }
+
+    #[test]
+    fn clicks_outside_regions_immediately() {
+        let mut guard = guard();
+        let now = Instant::now();
+        assert!(guard.trigger(vec2(10, 10), now));
+        assert_eq!(guard.pending(now), None);
+    }
+
+    #[test]
+    fn second_trigger_confirms() {
+        let mut guard = guard();
+        let now = Instant::now();
+        assert!(!guard.trigger(vec2(120, 110), now));
+        assert_eq!(guard.pending(now), Some(vec2(120, 110)));
+        let later = now + Duration::from_millis(1500);
+        assert!(guard.trigger(vec2(130, 105), later));
+        assert_eq!(guard.pending(later), None);
+    }
+
+    #[test]
+    fn pending_confirmation_expires() {
+        let mut guard = guard();
+        let now = Instant::now();
+        assert!(!guard.trigger(vec2(120, 110), now));
+        let later = now + Duration::from_millis(2500);
+        assert_eq!(guard.pending(later), None);
+        // a late trigger starts over instead of clicking
+        assert!(!guard.trigger(vec2(120, 110), later));
+        assert!(guard.trigger(vec2(120, 110), later + Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn drifting_away_needs_confirming_again() {
+        let mut guard = guard();
+        let now = Instant::now();
+        assert!(!guard.trigger(vec2(101, 101), now));
+        let later = now + Duration::from_millis(500);
+        assert!(!guard.trigger(vec2(145, 101), later));
+        assert_eq!(guard.pending(later), Some(vec2(145, 101)));
+
+        // drifting out of every region clicks there without confirmation
+        let again = later + Duration::from_millis(500);
+        assert!(!guard.trigger(vec2(101, 101), again));
+        let last = again + Duration::from_millis(500);
+        assert!(guard.trigger(vec2(101, 300), last));
+        assert_eq!(guard.pending(last), None);
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::Write;
+use std::panic;
+use std::sync::{Arc, Mutex, TryLockError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use cgmath::{Vector2, vec2};
+
+use profile::Profile;
+use secs;
+
+/// Absolute screen positions are snapped to a grid this many pixels wide
+/// before being recorded so a report doesn't reveal what the user was reading.
+const PRIVACY_GRID: f32 = 100.0;
+
+fn coarsen(pt: Vector2<f32>) -> Vector2<f32> {
+    vec2((pt.x / PRIVACY_GRID).round() * PRIVACY_GRID,
+         (pt.y / PRIVACY_GRID).round() * PRIVACY_GRID)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum PipelineEvent {
+    Head {
+        dt: f32,
+        head_delta: Vector2<f32>,
+        cursor_move: Vector2<f32>,
+        mouse_pt: Vector2<i32>,
+        dest: Vector2<i32>,
+        jumped: bool,
+        throwing: bool,
+    },
+    Gaze {
+        dt: f32,
+        gaze_pt: Vector2<f32>,
+        fixation_offset: Vector2<f32>,
+    },
+}
+
+impl PipelineEvent {
+    fn privacy_filtered(self) -> PipelineEvent {
+        match self {
+            PipelineEvent::Head { dt, head_delta, cursor_move, mouse_pt, dest, jumped, throwing } => {
+                let mouse = coarsen(vec2(mouse_pt.x as f32, mouse_pt.y as f32));
+                let offset = vec2((dest.x - mouse_pt.x) as f32, (dest.y - mouse_pt.y) as f32);
+                let mouse_pt = vec2(mouse.x as i32, mouse.y as i32);
+                PipelineEvent::Head {
+                    dt,
+                    head_delta,
+                    cursor_move,
+                    mouse_pt,
+                    dest: mouse_pt + vec2(offset.x as i32, offset.y as i32),
+                    jumped,
+                    throwing,
+                }
+            }
+            PipelineEvent::Gaze { dt, gaze_pt, fixation_offset } => {
+                PipelineEvent::Gaze {
+                    dt,
+                    gaze_pt: coarsen(gaze_pt),
+                    fixation_offset,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct DeviceStatus {
+    samples: u64,
+    last_sample: Option<Instant>,
+}
+
+impl DeviceStatus {
+    fn new() -> Self {
+        DeviceStatus {
+            samples: 0,
+            last_sample: None,
+        }
+    }
+}
+
+/// Keeps the last few seconds of pipeline state so a panic can be reported
+/// with enough context to debug fusion problems in the field.
+pub struct CrashRecorder {
+    window: Duration,
+    profile: String,
+    history: VecDeque<(Instant, PipelineEvent)>,
+    head: DeviceStatus,
+    gaze: DeviceStatus,
+}
+
+pub type SharedRecorder = Arc<Mutex<CrashRecorder>>;
+
+impl CrashRecorder {
+    pub fn new(window: Duration, profile: &Profile) -> SharedRecorder {
+        let recorder = CrashRecorder {
+            window,
+            profile: format!("{:#?}", profile),
+            history: VecDeque::new(),
+            head: DeviceStatus::new(),
+            gaze: DeviceStatus::new(),
+        };
+        Arc::new(Mutex::new(recorder))
+    }
+
+    pub fn record(&mut self, event: PipelineEvent) {
+        self.record_at(Instant::now(), event);
+    }
+
+    /// Like `record` with the clock passed in for simulations
+    pub fn record_at(&mut self, now: Instant, event: PipelineEvent) {
+        {
+            let status = match event {
+                PipelineEvent::Head { .. } => &mut self.head,
+                PipelineEvent::Gaze { .. } => &mut self.gaze,
+            };
+            status.samples += 1;
+            status.last_sample = Some(now);
+        }
+        self.history.push_back((now, event.privacy_filtered()));
+        while let Some(&(time, _)) = self.history.front() {
+            if now.duration_since(time) <= self.window {
+                break;
+            }
+            self.history.pop_front();
+        }
+    }
+
+    /// Events in the history window
+    pub fn buffered(&self) -> usize {
+        self.history.len()
+    }
+
+    fn report(&self, panic_msg: &str) -> String {
+        let now = Instant::now();
+        let ago = |time: Instant| secs(now.duration_since(time));
+        let mut out = String::new();
+        writeln!(out, "FusionMouse crash report").unwrap();
+        writeln!(out, "version: {}", env!("CARGO_PKG_VERSION")).unwrap();
+        writeln!(out, "panic: {}", panic_msg).unwrap();
+        writeln!(out, "\n== profile ==\n{}", self.profile).unwrap();
+        writeln!(out, "\n== devices ==").unwrap();
+        for &(name, status) in &[("head", self.head), ("gaze", self.gaze)] {
+            match status.last_sample {
+                Some(time) => {
+                    writeln!(out,
+                             "{}: {} samples, last {:.3}s ago",
+                             name,
+                             status.samples,
+                             ago(time))
+                            .unwrap()
+                }
+                None => writeln!(out, "{}: no samples", name).unwrap(),
+            }
+        }
+        writeln!(out,
+                 "\n== last {}s of pipeline state (positions snapped to {}px) ==",
+                 self.window.as_secs(),
+                 PRIVACY_GRID)
+                .unwrap();
+        for &(time, ref event) in &self.history {
+            writeln!(out, "-{:.4}s {:?}", ago(time), event).unwrap();
+        }
+        out
+    }
+}
+
+fn write_report(contents: &str) -> ::std::io::Result<::std::path::PathBuf> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let path = env::temp_dir().join(format!("fusion_mouse_crash_{}.txt", secs));
+    let mut file = File::create(&path)?;
+    file.write_all(contents.as_bytes())?;
+    Ok(path)
+}
+
+/// Installs a panic hook that writes a crash report before the default panic output
+pub fn install(recorder: SharedRecorder) {
+    let default_hook = panic::take_hook();
+    let reported = AtomicBool::new(false);
+    panic::set_hook(Box::new(move |info| {
+        // a pipeline panic also panics main when it joins, only report the first
+        if reported.swap(true, Ordering::SeqCst) {
+            default_hook(info);
+            return;
+        }
+        let msg = format!("{}", info);
+        // the panicking thread may be holding the lock, so never block on it
+        let contents = match recorder.try_lock() {
+            Ok(guard) => guard.report(&msg),
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner().report(&msg),
+            Err(TryLockError::WouldBlock) => format!("panic: {}\n(pipeline state was locked)", msg),
+        };
+        match write_report(&contents) {
+            Ok(path) => {
+                eprintln!("Wrote crash report to {}, please attach it to an issue.",
+                          path.display())
+            }
+            Err(e) => eprintln!("Couldn't write crash report: {}", e),
+        }
+        default_hook(info);
+    }));
+}
@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::Receiver;
+use std::time::Instant;
+
+use inputs::Input;
+use secs;
+
+#[derive(Clone, Debug)]
+pub struct DecimationParams {
+    pub tick_budget: f32, // seconds of work per sample before the loop counts as falling behind
+    pub hold_time: f32, // seconds to keep decimating after the last overrun
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DecimationStats {
+    /// ticks that took longer than the budget
+    pub overruns: u64,
+    pub head_dropped: u64,
+    pub gaze_dropped: u64,
+}
+
+/// Receives inputs for the fusion loop, and while it's falling behind on weak
+/// hardware skips stale tracker samples so latency stays bounded instead of the
+/// input threads queueing up behind it.
+///
+/// Only the newest head and gaze sample waiting is kept. Trackers report
+/// absolute poses and the loop computes `dt` from when it handles a sample, so
+/// the skipped samples' movement is still applied in one larger delta. Other
+/// inputs like triggers are never dropped.
+pub struct Decimator {
+    params: Option<DecimationParams>,
+    pending: VecDeque<Input>,
+    received_at: Option<Instant>,
+    last_overrun: Option<Instant>,
+    pub stats: DecimationStats,
+}
+
+impl Decimator {
+    pub const MAX_DRAIN: usize = 32; // inputs taken at once, more than the input pool buffers
+
+    pub fn new(params: Option<DecimationParams>) -> Self {
+        Decimator {
+            params,
+            pending: VecDeque::new(),
+            received_at: None,
+            last_overrun: None,
+            stats: DecimationStats::default(),
+        }
+    }
+
+    /// Blocks until the next input to handle
+    pub fn next(&mut self, rx: &Receiver<Input>) -> Input {
+        let input = self.take(rx, Instant::now());
+        self.received_at = Some(Instant::now());
+        input
+    }
+
+    /// Like `next` with the clock passed in for simulations, `done_at` being
+    /// when the last input was handled and `received_at` when this one is
+    pub fn next_at(&mut self, rx: &Receiver<Input>, done_at: Instant, received_at: Instant) -> Input {
+        let input = self.take(rx, done_at);
+        self.received_at = Some(received_at);
+        input
+    }
+
+    fn take(&mut self, rx: &Receiver<Input>, now: Instant) -> Input {
+        if let Some(received_at) = self.received_at {
+            self.measure(received_at, now);
+        }
+
+        match self.pending.pop_front() {
+            Some(input) => input,
+            None => {
+                let input = rx.recv().unwrap();
+                if self.is_behind(now) {
+                    self.drain(input, rx)
+                } else {
+                    input
+                }
+            }
+        }
+    }
+
+    /// Inputs taken from the channel but not handled yet
+    pub fn buffered(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn measure(&mut self, received_at: Instant, now: Instant) {
+        let budget = match self.params {
+            Some(ref params) => params.tick_budget,
+            None => return,
+        };
+        let work = secs(now.duration_since(received_at));
+        if work > budget {
+            if !self.is_behind(now) {
+                println!("Fusion loop falling behind ({:.1}ms per sample), skipping stale samples",
+                         work * 1000.0);
+            }
+            self.stats.overruns += 1;
+            self.last_overrun = Some(now);
+        }
+    }
+
+    fn is_behind(&self, now: Instant) -> bool {
+        match (&self.params, self.last_overrun) {
+            (&Some(ref params), Some(last_overrun)) => {
+                secs(now.duration_since(last_overrun)) <= params.hold_time
+            }
+            _ => false,
+        }
+    }
+
+    /// Takes everything waiting, replacing older tracker samples with newer ones
+    fn drain(&mut self, first: Input, rx: &Receiver<Input>) -> Input {
+        self.push(first);
+        while self.pending.len() < Self::MAX_DRAIN {
+            match rx.try_recv() {
+                Ok(input) => self.push(input),
+                Err(_) => break,
+            }
+        }
+        self.pending.pop_front().unwrap()
+    }
+
+    fn push(&mut self, input: Input) {
+        let stale = match input {
+            Input::LinuxTrackHead { .. } => {
+                self.pending.iter().position(|i| match *i {
+                    Input::LinuxTrackHead { .. } => true,
+                    _ => false,
+                })
+            }
+            Input::TobiiGaze { .. } => {
+                self.pending.iter().position(|i| match *i {
+                    Input::TobiiGaze { .. } => true,
+                    _ => false,
+                })
+            }
+            _ => None,
+        };
+        if let Some(index) = stale {
+            match self.pending.remove(index) {
+                Some(Input::LinuxTrackHead { .. }) => self.stats.head_dropped += 1,
+                Some(Input::TobiiGaze { .. }) => self.stats.gaze_dropped += 1,
+                _ => (),
+            }
+        }
+        self.pending.push_back(input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    fn head(yaw: f32) -> Input {
+        Input::LinuxTrackHead {
+            yaw,
+            pitch: 0.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+
+    #[test]
+    fn keeps_newest_samples_when_behind() {
+        let mut decimator = Decimator::new(Some(DecimationParams {
+                                                    tick_budget: 0.0,
+                                                    hold_time: 60.0,
+                                                }));
+        let (tx, rx) = mpsc::sync_channel(16);
+        tx.send(head(0.0)).unwrap();
+        decimator.next(&rx); // any work after this overruns the zero budget
+
+        let gaze = || Input::TobiiGaze { x: 0.5, y: 0.5 };
+        for input in vec![head(0.5), gaze(), head(0.5), gaze(), Input::Trigger, head(1.0)] {
+            tx.send(input).unwrap();
+        }
+        // only the last of each sample kind survives, behind the trigger
+        match decimator.next(&rx) {
+            Input::TobiiGaze { .. } => (),
+            _ => panic!("expected the newest gaze sample first"),
+        }
+        match decimator.next(&rx) {
+            Input::Trigger => (),
+            _ => panic!("expected the trigger"),
+        }
+        match decimator.next(&rx) {
+            Input::LinuxTrackHead { yaw, .. } => assert_eq!(yaw, 1.0),
+            _ => panic!("expected the newest head sample"),
+        }
+        assert_eq!(decimator.stats.head_dropped, 2);
+        assert_eq!(decimator.stats.gaze_dropped, 1);
+        assert!(decimator.stats.overruns > 0);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn keeps_everything_without_params() {
+        let mut decimator = Decimator::new(None);
+        let (tx, rx) = mpsc::sync_channel(16);
+        for _ in 0..4 {
+            tx.send(head(1.0)).unwrap();
+        }
+        for _ in 0..4 {
+            match decimator.next(&rx) {
+                Input::LinuxTrackHead { .. } => (),
+                _ => panic!("expected a head sample"),
+            }
+        }
+        assert_eq!(decimator.stats.head_dropped, 0);
+    }
+}
@@ -0,0 +1,17 @@
+use std::sync::mpsc::{SyncSender, Receiver};
+
+use inputs::{Input, InputAction};
+use line_input;
+
+/// Reads face counts from an external webcam face detector, one number per
+/// line, so camera and vision libraries stay out of FusionMouse and any
+/// detector that can print how many faces it sees works.
+pub fn listen(path: String, output: SyncSender<Input>, inbox: Receiver<InputAction>) {
+    line_input::listen(path, "face counts", output, inbox, |line| match line.parse::<u32>() {
+        Ok(count) => Some(Input::FaceCount { count }),
+        Err(_) => {
+            println!("Ignoring bad face count: {}", line);
+            None
+        }
+    })
+}
@@ -0,0 +1,210 @@
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{SyncSender, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use inputs::{Input, InputAction};
+
+const POLL_INTERVAL: u64 = 5; // milliseconds
+const DEBOUNCE_POLLS: u32 = 4; // polls a new reading must hold for before it counts
+
+fn write_attr<P: AsRef<Path>>(path: P, value: &str) -> io::Result<()> {
+    File::create(path)?.write_all(value.as_bytes())
+}
+
+/// Exporting a pin creates its attribute files asynchronously, and udev may
+/// still be fixing up their permissions for the `gpio` group, so retry a while.
+fn write_attr_retrying<P: AsRef<Path>>(path: P, value: &str) -> io::Result<()> {
+    let mut tries = 0;
+    loop {
+        match write_attr(&path, value) {
+            Err(ref e) if tries < 20 &&
+                          (e.kind() == ErrorKind::NotFound ||
+                           e.kind() == ErrorKind::PermissionDenied) => {
+                tries += 1;
+                thread::sleep(Duration::from_millis(50));
+            }
+            res => return res,
+        }
+    }
+}
+
+/// A gpiochip as listed in `/sys/class/gpio`
+struct GpioChip {
+    label: String,
+    base: u32,
+    ngpio: u32,
+}
+
+fn read_attr(path: PathBuf) -> io::Result<String> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Ok(contents.trim().to_owned())
+}
+
+fn list_chips() -> io::Result<Vec<GpioChip>> {
+    let mut chips = vec![];
+    for entry in fs::read_dir("/sys/class/gpio")? {
+        let dir = entry?.path();
+        if !dir.file_name().map_or(false, |name| name.to_string_lossy().starts_with("gpiochip")) {
+            continue;
+        }
+        let number = |attr: &str| -> io::Result<u32> {
+            read_attr(dir.join(attr))?
+                .parse()
+                .map_err(|_| io::Error::new(ErrorKind::InvalidData, format!("bad {}", attr)))
+        };
+        chips.push(GpioChip {
+                       label: read_attr(dir.join("label"))?,
+                       base: number("base")?,
+                       ngpio: number("ngpio")?,
+                   });
+    }
+    Ok(chips)
+}
+
+/// Turns a pin number as printed on a board's pinout, which is its line on
+/// the SoC's gpiochip, into a sysfs GPIO number. Newer kernels don't put that
+/// chip at base 0 anymore, on a Raspberry Pi it's at 512 since 6.6.
+fn sysfs_number(chips: &[GpioChip], pin: u32) -> Option<u32> {
+    let is_soc = |prefix: &str| chips.iter().find(|chip| chip.label.starts_with(prefix));
+    // the Pi 5's header is on the RP1 rather than the SoC itself
+    let chip = is_soc("pinctrl-rp1")
+        .or_else(|| is_soc("pinctrl-bcm"))
+        .or_else(|| chips.iter().min_by_key(|chip| chip.base));
+    match chip {
+        Some(chip) if pin < chip.ngpio => Some(chip.base + pin),
+        _ => None,
+    }
+}
+
+/// A switch on a GPIO pin, read through the Linux sysfs interface so no extra
+/// libraries are needed on boards like the Raspberry Pi.
+///
+/// The switch should connect the pin to ground with the pin's pull-up enabled
+/// (e.g. `gpio=17=ip,pu` in a Pi's `config.txt`), the usual wiring for
+/// accessibility switches with a 3.5mm jack, so the pin reads low while pressed.
+struct GpioSwitch {
+    pin: u32,
+    value: File,
+    exported: bool,
+}
+
+impl GpioSwitch {
+    fn open(line: u32) -> io::Result<GpioSwitch> {
+        let pin = match sysfs_number(&list_chips()?, line) {
+            Some(pin) => pin,
+            None => {
+                return Err(io::Error::new(ErrorKind::NotFound,
+                                          format!("no gpiochip has a line {}", line)))
+            }
+        };
+        let dir = PathBuf::from(format!("/sys/class/gpio/gpio{}", pin));
+        let exported = !dir.exists();
+        if exported {
+            write_attr("/sys/class/gpio/export", &pin.to_string())?;
+        }
+        write_attr_retrying(dir.join("direction"), "in")?;
+        write_attr_retrying(dir.join("active_low"), "1")?;
+        Ok(GpioSwitch {
+               pin,
+               value: File::open(dir.join("value"))?,
+               exported,
+           })
+    }
+
+    fn is_pressed(&mut self) -> io::Result<bool> {
+        let mut reading = [0u8; 1];
+        self.value.seek(SeekFrom::Start(0))?;
+        self.value.read_exact(&mut reading)?;
+        Ok(reading[0] == b'1')
+    }
+}
+
+impl Drop for GpioSwitch {
+    fn drop(&mut self) {
+        // leave pins exported by someone else, like a boot script, as they were
+        if self.exported {
+            let _ = write_attr("/sys/class/gpio/unexport", &self.pin.to_string());
+        }
+    }
+}
+
+fn wait_for_shutdown(inbox: Receiver<InputAction>) {
+    let _ = inbox.recv();
+}
+
+/// Sends a trigger each time the switch on `pin` is pressed
+pub fn listen(pin: u32, output: SyncSender<Input>, inbox: Receiver<InputAction>) {
+    let mut switch = match GpioSwitch::open(pin) {
+        Ok(switch) => switch,
+        Err(e) => {
+            println!("Couldn't set up GPIO pin {}: {}", pin, e);
+            return wait_for_shutdown(inbox);
+        }
+    };
+    println!("Listening for switch presses on GPIO pin {}", pin);
+
+    let mut pressed = false;
+    let mut changed_for = 0;
+    loop {
+        match inbox.try_recv() {
+            Ok(InputAction::Shutdown) => return,
+            Err(_) => (),
+        }
+
+        thread::sleep(Duration::from_millis(POLL_INTERVAL));
+        let reading = match switch.is_pressed() {
+            Ok(reading) => reading,
+            Err(e) => {
+                println!("Couldn't read GPIO pin {}: {}", pin, e);
+                return wait_for_shutdown(inbox);
+            }
+        };
+        if reading == pressed {
+            changed_for = 0;
+            continue;
+        }
+        changed_for += 1;
+        if changed_for >= DEBOUNCE_POLLS {
+            pressed = reading;
+            changed_for = 0;
+            if pressed && output.send(Input::Trigger).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chip(label: &str, base: u32, ngpio: u32) -> GpioChip {
+        GpioChip {
+            label: label.to_owned(),
+            base,
+            ngpio,
+        }
+    }
+
+    #[test]
+    fn finds_the_soc_chip_base() {
+        let old_kernel = [chip("pinctrl-bcm2711", 0, 58), chip("raspberrypi-exp-gpio", 504, 8)];
+        assert_eq!(sysfs_number(&old_kernel, 17), Some(17));
+        let new_kernel = [chip("raspberrypi-exp-gpio", 570, 8), chip("pinctrl-bcm2711", 512, 58)];
+        assert_eq!(sysfs_number(&new_kernel, 17), Some(529));
+        let pi5 = [chip("pinctrl-bcm2712", 512, 32), chip("pinctrl-rp1", 571, 54)];
+        assert_eq!(sysfs_number(&pi5, 17), Some(588));
+    }
+
+    #[test]
+    fn falls_back_to_the_lowest_chip() {
+        let other_board = [chip("gpio-b", 64, 32), chip("gpio-a", 32, 32)];
+        assert_eq!(sysfs_number(&other_board, 5), Some(37));
+        assert_eq!(sysfs_number(&other_board, 32), None);
+        assert_eq!(sysfs_number(&[], 5), None);
+    }
+}
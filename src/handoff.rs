@@ -0,0 +1,214 @@
+//! Integration with external click tools like dwell clickers.
+//!
+//! In handoff mode FusionMouse only does pointing and never clicks. Clients
+//! connect to a unix socket and receive one line per event:
+//!
+//! ```text
+//! stable <x> <y>     cursor has stayed within a small radius for a while
+//! moving <x> <y>     cursor left the stable radius
+//! fixation <x> <y>   gaze settled on a new fixation
+//! ```
+//!
+//! A client sends `hold` before it clicks and `release` afterwards, while any
+//! client holds the cursor FusionMouse won't move it so the click lands where
+//! the tool expects. Sending `swap-roles` switches between gaze jumps and head
+//! jumps, for tools that offer it as a command or hotkey.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use cgmath::{Vector2, MetricSpace};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HandoffEvent {
+    Stable(Vector2<f32>),
+    Moving(Vector2<f32>),
+    Fixation(Vector2<f32>),
+}
+
+impl HandoffEvent {
+    fn to_line(&self) -> String {
+        let (name, pt) = match *self {
+            HandoffEvent::Stable(pt) => ("stable", pt),
+            HandoffEvent::Moving(pt) => ("moving", pt),
+            HandoffEvent::Fixation(pt) => ("fixation", pt),
+        };
+        format!("{} {} {}\n", name, pt.x.round(), pt.y.round())
+    }
+}
+
+/// Detects when a point stays within `radius` pixels for at least `min_time` seconds
+pub struct StabilityDetector {
+    radius: f32,
+    min_time: f32,
+    anchor: Option<Vector2<f32>>,
+    still_for: f32,
+    stable: bool,
+}
+
+impl StabilityDetector {
+    pub fn new(radius: f32, min_time: f32) -> Self {
+        StabilityDetector {
+            radius,
+            min_time,
+            anchor: None,
+            still_for: 0.0,
+            stable: false,
+        }
+    }
+
+    pub fn update(&mut self, pt: Vector2<f32>, dt: f32) -> Option<HandoffEvent> {
+        let anchor = match self.anchor {
+            Some(anchor) if anchor.distance(pt) <= self.radius => anchor,
+            _ => {
+                self.anchor = Some(pt);
+                self.still_for = 0.0;
+                if self.stable {
+                    self.stable = false;
+                    return Some(HandoffEvent::Moving(pt));
+                }
+                return None;
+            }
+        };
+
+        self.still_for += dt;
+        if !self.stable && self.still_for >= self.min_time {
+            self.stable = true;
+            return Some(HandoffEvent::Stable(anchor));
+        }
+        None
+    }
+}
+
+pub struct Handoff {
+    clients: Arc<Mutex<Vec<UnixStream>>>,
+    holds: Arc<AtomicUsize>,
+    swap_roles: Arc<AtomicBool>,
+    cursor: StabilityDetector,
+    last_fixation: Option<Vector2<f32>>,
+}
+
+impl Handoff {
+    const STABLE_RADIUS: f32 = 15.0; // pixels
+    const STABLE_TIME: f32 = 0.1; // seconds
+    const FIXATION_RADIUS: f32 = 40.0; // pixels a fixation must move to be reported again
+
+    pub fn listen(path: &str) -> io::Result<Handoff> {
+        // clear a stale socket from a previous run, but never a file given by mistake
+        match fs::symlink_metadata(path) {
+            Ok(ref metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+            Ok(_) => {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                                          format!("{} already exists and isn't a socket", path)))
+            }
+            Err(_) => (),
+        }
+        let listener = UnixListener::bind(path)?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let holds = Arc::new(AtomicUsize::new(0));
+        let swap_roles = Arc::new(AtomicBool::new(false));
+
+        let accept_clients = clients.clone();
+        let accept_holds = holds.clone();
+        let accept_swap_roles = swap_roles.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        println!("Handoff connection failed: {:?}", e);
+                        continue;
+                    }
+                };
+                // a client that stops reading shouldn't be able to stall the pipeline
+                let _ = stream.set_write_timeout(Some(Duration::from_millis(5)));
+                match stream.try_clone() {
+                    Ok(reader) => {
+                        let holds = accept_holds.clone();
+                        let swap_roles = accept_swap_roles.clone();
+                        thread::spawn(move || read_commands(reader, holds, swap_roles));
+                    }
+                    Err(e) => println!("Handoff connection failed: {:?}", e),
+                }
+                accept_clients.lock().unwrap().push(stream);
+            }
+        });
+
+        Ok(Handoff {
+            clients,
+            holds,
+            swap_roles,
+            cursor: StabilityDetector::new(Self::STABLE_RADIUS, Self::STABLE_TIME),
+            last_fixation: None,
+        })
+    }
+
+    /// true while a client is clicking and the cursor must stay put
+    pub fn is_held(&self) -> bool {
+        self.holds.load(Ordering::SeqCst) > 0
+    }
+
+    /// true once after a client asked to swap the fusion roles
+    pub fn take_role_swap(&self) -> bool {
+        self.swap_roles.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn update_cursor(&mut self, cursor: Vector2<f32>, dt: f32) {
+        if let Some(event) = self.cursor.update(cursor, dt) {
+            self.send(event);
+        }
+    }
+
+    pub fn update_gaze(&mut self, fixation: Vector2<f32>) {
+        let moved = match self.last_fixation {
+            Some(last) => last.distance(fixation) > Self::FIXATION_RADIUS,
+            None => true,
+        };
+        if moved {
+            self.last_fixation = Some(fixation);
+            self.send(HandoffEvent::Fixation(fixation));
+        }
+    }
+
+    fn send(&self, event: HandoffEvent) {
+        let line = event.to_line();
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|client| {
+            let mut client = client;
+            client.write_all(line.as_bytes()).is_ok()
+        });
+    }
+}
+
+fn read_commands(stream: UnixStream, holds: Arc<AtomicUsize>, swap_roles: Arc<AtomicBool>) {
+    let mut holding = false;
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        match line.trim() {
+            "hold" if !holding => {
+                holding = true;
+                holds.fetch_add(1, Ordering::SeqCst);
+            }
+            "release" if holding => {
+                holding = false;
+                holds.fetch_sub(1, Ordering::SeqCst);
+            }
+            "hold" | "release" => (),
+            "swap-roles" => swap_roles.store(true, Ordering::SeqCst),
+            other => println!("Unknown handoff command: {}", other),
+        }
+    }
+    // don't leave the cursor frozen if a client disconnects mid-click
+    if holding {
+        holds.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -2,11 +2,30 @@ use std::sync::mpsc;
 use std::thread;
 
 pub enum Input {
-    LinuxTrackHead { yaw: f32, pitch: f32 },
+    /// head rotation in degrees and translation in tracker units
+    LinuxTrackHead {
+        yaw: f32,
+        pitch: f32,
+        x: f32,
+        y: f32,
+        z: f32,
+    },
     TobiiGaze { x: f32, y: f32 },
+    /// faces seen by a webcam based tracker
+    FaceCount { count: u32 },
+    /// a tracker recovered from losing tracking or its connection
+    Reconnected { tracker: Tracker },
+    /// a switch or other trigger asking for a click at the cursor
+    Trigger,
     Shutdown,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tracker {
+    Head,
+    Gaze,
+}
+
 pub enum InputAction {
     // Pause,
     // Resume,
@@ -24,8 +43,12 @@ pub struct InputPool {
 }
 
 impl InputPool {
+    /// Inputs that can wait for the fusion loop before input threads block,
+    /// about 100ms of both trackers so a slow loop has a backlog to decimate
+    pub const BUFFER: usize = 16;
+
     pub fn new() -> (InputPool, mpsc::Receiver<Input>) {
-        let (tx, rx) = mpsc::sync_channel::<Input>(0); // TODO choose best constant
+        let (tx, rx) = mpsc::sync_channel::<Input>(Self::BUFFER);
         let pool = InputPool {
             threads: vec![],
             sender: tx,
@@ -51,7 +74,8 @@ impl InputPool {
 impl Drop for InputPool {
     fn drop(&mut self) {
         for thread in &self.threads {
-            thread.inbox.send(InputAction::Shutdown).unwrap();
+            // threads that gave up on their tracker have already dropped their inbox
+            let _ = thread.inbox.send(InputAction::Shutdown);
         }
 
         for thread in &mut self.threads {
@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::sync::mpsc::{self, SyncSender, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use inputs::{Input, InputAction};
+
+/// Turns lines written by another program into inputs, reading from a file or
+/// named pipe, or stdin when `path` is `-`. This keeps hardware and vision
+/// libraries out of FusionMouse, anything that can print a line per event
+/// works. `parse` returns `None` for lines to ignore.
+pub fn listen<F>(path: String,
+                 what: &str,
+                 output: SyncSender<Input>,
+                 inbox: Receiver<InputAction>,
+                 mut parse: F)
+    where F: FnMut(&str) -> Option<Input>
+{
+    let source: Box<dyn Read + Send> = if path == "-" {
+        Box::new(io::stdin())
+    } else {
+        match File::open(&path) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                println!("Couldn't open {} from {}: {}", what, path, e);
+                let _ = inbox.recv();
+                return;
+            }
+        }
+    };
+
+    // reads block, so they happen on their own thread to keep noticing shutdown
+    let (lines_tx, lines) = mpsc::channel();
+    thread::spawn(move || for line in BufReader::new(source).lines() {
+                      match line {
+                          Ok(line) => {
+                              if lines_tx.send(line).is_err() {
+                                  break;
+                              }
+                          }
+                          Err(_) => break,
+                      }
+                  });
+
+    loop {
+        match inbox.try_recv() {
+            Ok(InputAction::Shutdown) => return,
+            Err(_) => (),
+        }
+        match lines.recv_timeout(Duration::from_millis(100)) {
+            Ok(line) => {
+                if let Some(input) = parse(line.trim()) {
+                    if output.send(input).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => {
+                println!("No more {} from {}", what, path);
+                let _ = inbox.recv();
+                return;
+            }
+        }
+    }
+}
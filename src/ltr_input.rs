@@ -5,16 +5,23 @@ use std::mem;
 use std::os::raw;
 use std::sync::mpsc::{SyncSender, Receiver};
 
-use inputs::{Input, InputAction};
+use inputs::{Input, InputAction, Tracker};
 use signpost;
 
-unsafe fn get_one_pose() -> Result<Pose, Status> {
+enum PoseError {
+    /// no pose within the wait timeout, the tracker lost tracking or stopped
+    Timeout,
+    /// a single frame without all the blobs, common when a marker glints
+    Dropout,
+}
+
+unsafe fn get_one_pose() -> Result<Pose, PoseError> {
     let res = linuxtrack_wait(1000); // 1 second timeout
     // println!("waited  {:?}", res);
     if res != 1 {
         let status = linuxtrack_get_tracking_state();
         println!("Status: {:?}", status);
-        return Err(status);
+        return Err(PoseError::Timeout);
     }
     signpost::start(3, &[0, 0, 0, signpost::Color::Green as usize]);
 
@@ -30,12 +37,13 @@ unsafe fn get_one_pose() -> Result<Pose, Status> {
     // println!("Blobs: {:?}", blobs_read);
 
     if res != 1 || blobs_read < 3 {
-        return Err(STATUS_RUNNING);
+        return Err(PoseError::Dropout);
     }
     return Ok(pose);
 }
 
 unsafe fn input_loop(output: SyncSender<Input>, inbox: Receiver<InputAction>) {
+    let mut lost_tracking = false;
     loop {
         match inbox.try_recv() {
             Ok(InputAction::Shutdown) => return,
@@ -45,12 +53,26 @@ unsafe fn input_loop(output: SyncSender<Input>, inbox: Receiver<InputAction>) {
 
         let pose = match get_one_pose() {
             Ok(pose) => pose,
-            Err(_) => continue,
+            Err(PoseError::Timeout) => {
+                lost_tracking = true;
+                continue;
+            }
+            // the filters and the pipeline's gap check ride out dropped frames
+            Err(PoseError::Dropout) => continue,
         };
+        if lost_tracking {
+            lost_tracking = false;
+            output
+                .send(Input::Reconnected { tracker: Tracker::Head })
+                .expect("shutdown should come before channel close");
+        }
 
         let input = Input::LinuxTrackHead {
             yaw: pose.raw_yaw,
             pitch: pose.raw_pitch,
+            x: pose.raw_tx,
+            y: pose.raw_ty,
+            z: pose.raw_tz,
         };
         output
             .send(input)
@@ -1,7 +1,10 @@
+#[cfg(feature = "linuxtrack")]
 extern crate linuxtrack_sys;
+#[cfg(feature = "tobii")]
 extern crate tobii_sys;
 extern crate cgmath;
 extern crate enigo;
+#[cfg(feature = "signposts")]
 extern crate signpost;
 
 #[cfg(feature = "viz-2d")]
@@ -13,9 +16,31 @@ extern crate cocoa;
 extern crate objc;
 
 mod inputs;
+#[cfg(feature = "linuxtrack")]
 mod ltr_input;
+#[cfg(feature = "tobii")]
 mod tobii_input;
+#[cfg(feature = "gpio")]
+mod gpio_input;
+mod synthetic_input;
+mod profile;
+mod crash_report;
+mod handoff;
+mod signals;
 mod transforms;
+mod pipeline;
+mod soak;
+mod privacy;
+mod line_input;
+mod face_input;
+mod trail;
+mod metrics;
+mod click_guard;
+mod decimation;
+mod scenario;
+#[cfg(not(feature = "signposts"))]
+#[path = "no_signpost.rs"]
+mod signpost;
 
 #[cfg(feature = "viz-2d")]
 mod viz_2d;
@@ -24,59 +49,210 @@ use viz_2d::{DebugSender, DebugWindow, DebugFrame, DebugPoint};
 #[cfg(not(feature = "viz-2d"))]
 struct DebugSender();
 
-use cgmath::{vec2, Vector2};
-use enigo::{Enigo, MouseControllable};
+use cgmath::{vec2, vec3, Vector2};
+use enigo::{Enigo, MouseControllable, MouseButton};
 
 use std::sync::mpsc::Receiver;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use std::mem;
-use std::cmp::{min, max};
 use std::thread;
+use std::env;
+use std::process;
+use std::io::{self, Read};
+use std::fs::File;
 
-use inputs::{InputPool, Input};
+use inputs::{InputPool, Input, Tracker};
 use transforms::*;
+use profile::Profile;
+use pipeline::{Pipeline, HeadPose};
+use crash_report::{CrashRecorder, SharedRecorder, PipelineEvent};
+use handoff::Handoff;
+use privacy::{PrivacyGuard, PrivacyParams};
+use trail::TrailLogger;
+use metrics::{Metrics, SharedMetrics};
+use click_guard::{ClickGuard, Region};
+use decimation::Decimator;
+
+fn secs(dur: Duration) -> f32 {
+    dur.as_secs() as f32 + dur.subsec_nanos() as f32 * 1.0e-9
+}
 
 fn calc_dt(tick: Instant, last_tick: &mut Instant) -> f32 {
-    let dur = tick.duration_since(*last_tick);
-    let dt = dur.as_secs() as f32 + dur.subsec_nanos() as f32 * 1.0e-9;
+    let dt = secs(tick.duration_since(*last_tick));
     mem::replace(last_tick, tick);
     dt
 }
 
-fn run_pipeline(rx: Receiver<Input>, debug: DebugSender) {
-    // configuration
-    let accel = Acceleration {
-        cd_min: 8.0, // min gain
-        cd_max: 65.0, // max gain
-        v_min: 0.0004, // input velocity lower bound
-        v_max: 0.0025, // input velocity upper bound
-        lambda: 1000.0, // slope of curve at inflection point
-        ratio: 0.7, // where inflection lies between v_min and v_max
-    };
-    let polymouse_params = PolyMouseParams {
-        min_jump: 100.0,
-        speed_expand_factor: 0.0, // TODO translate delta->speed
-        head_smoothing_factor: 1.0, // TODO tune for dt
-        throw_thresh_speed: 300.0, // pixels per second
-        throw_speed: 8000.0, // pixels per second
-        small_jump_factor: 0.75,
-    };
+struct Options {
+    /// use simulated trackers so the app can be tried without the hardware
+    demo: bool,
+    /// socket path for external click tools, see `handoff`
+    handoff: Option<String>,
+    /// calibration samples to fit a geometry correction mesh to
+    geometry_samples: Option<String>,
+    profile: Profile,
+    /// overrides the profile's cursor rounding
+    rounding: Option<Rounding>,
+    /// simulated hours to soak test the pipeline for instead of running
+    soak_hours: Option<f32>,
+    /// file to log cursor and gaze positions to for overlaying on screen recordings
+    trail: Option<String>,
+    /// address to serve metrics on over HTTP
+    metrics_addr: Option<String>,
+    /// extra regions where clicks need confirmation
+    confirm_regions: Vec<Region>,
+    /// GPIO pin with a switch that triggers clicks
+    gpio_switch: Option<u32>,
+    /// file or pipe a face detector writes face counts to, `-` for stdin
+    faces: Option<String>,
+    /// file or pipe a switch interface writes a line to per click, `-` for stdin
+    trigger: Option<String>,
+    /// scenario files to run instead of running
+    scenarios: Vec<String>,
+}
+
+impl Options {
+    fn from_args() -> Options {
+        let mut options = Options {
+            demo: false,
+            handoff: None,
+            geometry_samples: None,
+            profile: Profile::default(),
+            rounding: None,
+            soak_hours: None,
+            trail: None,
+            metrics_addr: None,
+            confirm_regions: vec![],
+            gpio_switch: None,
+            faces: None,
+            trigger: None,
+            scenarios: vec![],
+        };
+        for arg in env::args().skip(1) {
+            let mut parts = arg.splitn(2, '=');
+            match (parts.next().unwrap(), parts.next()) {
+                ("--demo", None) => options.demo = true,
+                ("--handoff", Some(path)) => options.handoff = Some(path.to_owned()),
+                ("--trail", Some(path)) => options.trail = Some(path.to_owned()),
+                ("--faces", Some(path)) => options.faces = Some(path.to_owned()),
+                ("--trigger", Some(path)) => options.trigger = Some(path.to_owned()),
+                ("--metrics", Some(addr)) => options.metrics_addr = Some(addr.to_owned()),
+                ("--scenario", Some(path)) => options.scenarios.push(path.to_owned()),
+                ("--confirm-region", Some(region)) => {
+                    match Region::parse(region) {
+                        Some(region) => options.confirm_regions.push(region),
+                        None => {
+                            println!("--confirm-region needs x,y,width,height, got {}", region);
+                            process::exit(1);
+                        }
+                    }
+                }
+                ("--gpio-switch", Some(pin)) => {
+                    options.gpio_switch = match pin.parse() {
+                        Ok(pin) => Some(pin),
+                        Err(_) => {
+                            println!("--gpio-switch needs a pin number, got {}", pin);
+                            process::exit(1);
+                        }
+                    }
+                }
+                ("--geometry-samples", Some(path)) => {
+                    options.geometry_samples = Some(path.to_owned())
+                }
+                ("--soak", Some(hours)) => {
+                    options.soak_hours = match hours.parse::<f32>() {
+                        Ok(hours) if hours > 0.0 && hours.is_finite() => Some(hours),
+                        _ => {
+                            println!("--soak needs a positive number of hours, got {}", hours);
+                            process::exit(1);
+                        }
+                    }
+                }
+                ("--profile", Some(name)) => {
+                    options.profile = match Profile::by_name(name) {
+                        Some(profile) => profile,
+                        None => {
+                            println!("Unknown profile {}, choose one of: {}",
+                                     name,
+                                     Profile::names().join(", "));
+                            process::exit(1);
+                        }
+                    }
+                }
+                ("--rounding", Some(name)) => {
+                    options.rounding = match Rounding::by_name(name) {
+                        Some(rounding) => Some(rounding),
+                        None => {
+                            println!("Unknown rounding {}, choose one of: {}",
+                                     name,
+                                     Rounding::names().join(", "));
+                            process::exit(1);
+                        }
+                    }
+                }
+                _ => {
+                    println!("Unknown argument: {}", arg);
+                    println!("Usage: fusion_mouse [--demo] [--profile=<name>] [--rounding=<name>] \
+                              [--handoff=<socket path>] [--trail=<file>] \
+                              [--metrics=<address>] [--confirm-region=<x,y,w,h>]... \
+                              [--gpio-switch=<pin>] [--trigger=<file or ->] \
+                              [--faces=<file or ->] \
+                              [--geometry-samples=<file>] [--soak=<hours>] \
+                              [--scenario=<file>]...");
+                    process::exit(1);
+                }
+            }
+        }
+        options
+    }
+}
 
+/// Reads calibration samples, one per line as `measured_x measured_y true_x true_y`
+/// in normalized screen coordinates.
+fn load_geometry_samples(path: &str) -> io::Result<Vec<(Vector2<f32>, Vector2<f32>)>> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    let mut samples = Vec::new();
+    for line in contents.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let nums: Result<Vec<f32>, _> = line.split_whitespace().map(|n| n.parse()).collect();
+        match nums {
+            Ok(ref nums) if nums.len() == 4 => {
+                samples.push((vec2(nums[0], nums[1]), vec2(nums[2], nums[3])))
+            }
+            _ => println!("Skipping bad geometry sample: {}", line),
+        }
+    }
+    if samples.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "no valid samples"));
+    }
+    Ok(samples)
+}
+
+fn run_pipeline(rx: Receiver<Input>,
+                debug: DebugSender,
+                profile: Profile,
+                recorder: SharedRecorder,
+                metrics: SharedMetrics,
+                mut handoff: Option<Handoff>,
+                mut trail: Option<TrailLogger>) {
     // input state
-    let mut raw_head_pose: Vector2<f32> = vec2(0.0, 0.0);
+    let mut raw_head_pose = HeadPose {
+        rotation: vec2(0.0, 0.0),
+        translation: vec3(0.0, 0.0, 0.0),
+    };
     let mut raw_gaze: Vector2<f32> = vec2(0.0, 0.0);
 
     // pipeline state
     let mut last_head_tick = Instant::now();
     let mut last_gaze_tick = Instant::now();
-    let mut head_filter = VecOneEuroFilter::new(6.0, 1000.0, 1.0);
-    let mut last_head_pose: Option<Vector2<f32>> = None;
-
-    let mut poly_mouse = PolyMouseTransform::new(polymouse_params.clone());
-
-    let mut fixation_filter = FixationFilter::new(0.03, 150.0);
-    let mut gaze_pt: Vector2<f32> = vec2(0.0, 0.0);
-    let mut px_gaze: Vector2<f32> = vec2(0.0, 0.0);
+    let mut privacy = profile.privacy.clone().map(PrivacyGuard::new);
+    let mut click_guard = ClickGuard::new(profile.click_confirm.clone());
+    let mut decimator = Decimator::new(profile.decimation.clone());
+    let mut pipeline = Pipeline::new(profile);
+    let mut scroll_rounder = AccumulatingRounder::new();
 
     let mut enigo = Enigo::new();
 
@@ -84,103 +260,313 @@ fn run_pipeline(rx: Receiver<Input>, debug: DebugSender) {
         // update input state =========================
         let mut tick_gaze = false;
         let mut tick_head = false;
-        match rx.recv().unwrap() {
-            Input::LinuxTrackHead { yaw, pitch } => {
-                raw_head_pose = vec2(yaw, pitch) * -1.0;
+        match decimator.next(&rx) {
+            Input::LinuxTrackHead { yaw, pitch, x, y, z } => {
+                raw_head_pose = HeadPose {
+                    rotation: vec2(yaw, pitch) * -1.0,
+                    translation: vec3(x, y, z),
+                };
                 tick_head = true;
             }
             Input::TobiiGaze { x, y } => {
                 raw_gaze = vec2(x, y);
                 tick_gaze = true;
             }
+            Input::FaceCount { count } => {
+                if let Some(ref mut privacy) = privacy {
+                    privacy.faces(count);
+                }
+            }
+            Input::Reconnected { tracker: Tracker::Head } => pipeline.reset_head(),
+            Input::Reconnected { tracker: Tracker::Gaze } => pipeline.reset_gaze(),
+            Input::Trigger => {
+                let paused = privacy.as_ref().map_or(false, |p| p.is_paused());
+                // in handoff mode the external tool does all the clicking
+                if !paused && handoff.is_none() {
+                    let (mouse_x, mouse_y) = Enigo::mouse_location();
+                    if click_guard.trigger(vec2(mouse_x, mouse_y), Instant::now()) {
+                        enigo.mouse_click(MouseButton::Left);
+                    }
+                }
+            }
             Input::Shutdown => break,
         }
+        metrics.lock().unwrap().decimation = decimator.stats.clone();
         let _signpost = signpost::AutoTrace::new(1, &[0, 0, 0, signpost::Color::Blue as usize]);
 
         let tick = Instant::now();
         let (display_width, display_height) = Enigo::main_display_size();
+        let display = vec2(display_width as f32, display_height as f32);
 
         // compute pipeline results ===================
         if tick_head {
             let dt = calc_dt(tick, &mut last_head_tick);
-            let smoothed_head = head_filter.filter(raw_head_pose, dt);
-            // let smoothed_head = raw_head_pose;
-
-            let head_delta = match last_head_pose {
-                Some(last_pose) => smoothed_head - last_pose,
-                None => vec2(0.0, 0.0),
-            };
-            last_head_pose = Some(smoothed_head);
-
-            let head_cursor_move = vec2(accel.transform(head_delta.x, dt),
-                                        accel.transform(head_delta.y, dt));
-
+            if privacy.as_ref().map_or(false, |p| p.is_paused()) {
+                // emit and record nothing while someone else might be in control
+                continue;
+            }
+            let swap_requested = handoff.as_ref().map_or(false, |h| h.take_role_swap());
+            if signals::take_role_swap() || swap_requested {
+                pipeline.swap_roles();
+                println!("Swapped roles: {:?}", pipeline.roles());
+            }
             let (mouse_x, mouse_y) = Enigo::mouse_location();
             let mouse_pt = vec2(mouse_x, mouse_y);
-            let dest = poly_mouse.transform(gaze_pt, mouse_pt, head_cursor_move, dt);
-            let confined = vec2(max(0, min(display_width as i32, dest.x)),
-                                max(0, min(display_height as i32, dest.y)));
+            let update = pipeline.head(raw_head_pose, dt, mouse_pt, display);
+            recorder.lock().unwrap().record(PipelineEvent::Head {
+                dt,
+                head_delta: update.head_delta,
+                cursor_move: update.cursor_move,
+                mouse_pt,
+                dest: update.dest,
+                jumped: update.jumped,
+                throwing: pipeline.poly_mouse.is_throwing(),
+            });
+            {
+                let mut metrics = metrics.lock().unwrap();
+                metrics.effort.head(update.head_delta,
+                                    update.cursor_move,
+                                    update.jumped,
+                                    pipeline.poly_mouse.is_throwing(),
+                                    dt);
+            }
 
-            if confined != mouse_pt {
+            let confined = update.confined;
+            let held = handoff.as_ref().map_or(false, |h| h.is_held());
+            if confined != mouse_pt && !held {
                 enigo.mouse_move_to(confined.x, confined.y);
             }
+            let lines = scroll_rounder.round(update.scroll);
+            if lines != 0 && !held {
+                enigo.mouse_scroll_y(lines);
+            }
+            let cursor = if held { mouse_pt } else { confined };
+            if let Some(ref mut handoff) = handoff {
+                handoff.update_cursor(vec2(cursor.x as f32, cursor.y as f32), dt);
+            }
+            if let Some(ref mut trail) = trail {
+                trail.cursor(cursor);
+            }
 
             // debugging =====================
             #[cfg(feature = "viz-2d")]
             {
+                let params = &pipeline.params;
                 let mut debug_frame = DebugFrame {
                     points: Vec::with_capacity(4),
                     display_width: display_width as f32,
                     display_height: display_height as f32,
                 };
                 let circle = DebugPoint {
-                    offset: [dest.x as f32, dest.y as f32],
+                    offset: [update.dest.x as f32, update.dest.y as f32],
                     color: [0.0, 1.0, 0.0],
-                    size: polymouse_params.min_jump*2.0,
+                    size: params.min_jump*2.0,
                 };
                 debug_frame.points.push(circle);
                 let circle2 = DebugPoint {
-                    offset: poly_mouse.last_jump_destination.into(),
+                    offset: pipeline.poly_mouse.last_jump_destination.into(),
                     color: [0.0, 1.0, 0.0],
-                    size: polymouse_params.min_jump*polymouse_params.small_jump_factor*2.0,
+                    size: params.min_jump*params.small_jump_factor*2.0,
                 };
                 debug_frame.points.push(circle2);
-                debug_frame.add_point(gaze_pt, [1.0, 0.0, 0.0]);
-                debug_frame.add_point(px_gaze, [1.0, 0.0, 1.0]);
+                debug_frame.add_point(pipeline.gaze_pt, [1.0, 0.0, 0.0]);
+                debug_frame.add_point(pipeline.px_gaze, [1.0, 0.0, 1.0]);
+                if let Some(pending) = click_guard.pending(tick) {
+                    debug_frame.points.push(DebugPoint {
+                        offset: [pending.x as f32, pending.y as f32],
+                        color: [1.0, 0.5, 0.0],
+                        size: 40.0,
+                    });
+                }
+                if let Some(puck) = pipeline.puck() {
+                    debug_frame.points.push(DebugPoint {
+                        offset: puck.center().into(),
+                        color: [0.0, 1.0, 1.0],
+                        size: puck.size.x,
+                    });
+                    debug_frame.add_point(puck.pos, [0.0, 1.0, 1.0]);
+                }
                 debug.send(debug_frame);
             }
             #[cfg(not(feature = "viz-2d"))]
-            let _silence_warnings = (&px_gaze, &debug);
+            let _silence_warnings = &debug;
         }
 
         if tick_gaze {
             let dt = calc_dt(tick, &mut last_gaze_tick);
-            px_gaze = vec2(raw_gaze.x * (display_width as f32),
-                           raw_gaze.y * (display_height as f32));
-            gaze_pt = fixation_filter.transform(px_gaze, dt);
-            // println!("GAZE {:?}", gaze_pt);
+            pipeline.gaze(raw_gaze, dt, display);
+            if let Some(ref mut privacy) = privacy {
+                if privacy.is_paused() {
+                    let (mouse_x, mouse_y) = Enigo::mouse_location();
+                    let cursor = vec2(mouse_x as f32, mouse_y as f32);
+                    if privacy.gaze(pipeline.gaze_pt, cursor, dt) {
+                        pipeline.reset();
+                    }
+                    continue;
+                }
+            }
+            recorder.lock().unwrap().record(PipelineEvent::Gaze {
+                dt,
+                gaze_pt: pipeline.px_gaze,
+                fixation_offset: pipeline.gaze_pt - pipeline.px_gaze,
+            });
+            if let Some(ref mut handoff) = handoff {
+                handoff.update_gaze(pipeline.gaze_pt);
+            }
+            if let Some(ref mut trail) = trail {
+                trail.gaze(pipeline.gaze_pt);
+            }
         }
     }
+
+    print!("{}", metrics.lock().unwrap().summary());
 }
 
 fn main() {
     println!("Hello, world!");
+    let options = Options::from_args();
+
+    let mut profile = options.profile;
+    if let Some(rounding) = options.rounding {
+        profile.polymouse.rounding = rounding;
+    }
+    profile.click_confirm.regions.extend(options.confirm_regions);
+    if options.faces.is_some() {
+        profile.privacy.get_or_insert(PrivacyParams {
+            confirm_time: 1.0,
+            confirm_radius: 60.0,
+        });
+    }
+    if let Some(ref path) = options.geometry_samples {
+        match load_geometry_samples(path) {
+            Ok(samples) => {
+                let geometry = profile.geometry.get_or_insert(GeometryParams {
+                    keystone: None,
+                    curve_angle: 0.0,
+                    mesh: None,
+                });
+                let mesh = GeometryCorrection::fit_mesh(geometry, 8, 5, &samples);
+                geometry.mesh = Some(mesh);
+            }
+            Err(e) => {
+                println!("Couldn't read geometry samples from {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(hours) = options.soak_hours {
+        let passed = soak::run(profile, hours, 1);
+        process::exit(if passed { 0 } else { 1 });
+    }
+    if !options.scenarios.is_empty() {
+        let passed = scenario::run_files(&options.scenarios);
+        process::exit(if passed { 0 } else { 1 });
+    }
+
     let (mut pool, rx) = InputPool::new();
-    pool.spawn(ltr_input::listen);
-    pool.spawn(tobii_input::listen);
+    if options.demo {
+        println!("Demo mode: a simulated user is driving the cursor");
+        if cfg!(not(feature = "viz-2d")) {
+            println!("Build with --features viz-2d to see the gaze and jump overlays");
+        }
+        pool.spawn(synthetic_input::listen);
+    } else {
+        if cfg!(not(feature = "linuxtrack")) {
+            println!("Built without the linuxtrack feature, head tracking is off");
+        }
+        if cfg!(not(feature = "tobii")) {
+            println!("Built without the tobii feature, gaze tracking is off");
+        }
+        #[cfg(feature = "linuxtrack")]
+        {
+            pool.spawn(ltr_input::listen);
+        }
+        #[cfg(feature = "tobii")]
+        {
+            pool.spawn(tobii_input::listen);
+        }
+    }
+    if let Some(pin) = options.gpio_switch {
+        #[cfg(feature = "gpio")]
+        {
+            pool.spawn(move |output, inbox| gpio_input::listen(pin, output, inbox));
+        }
+        #[cfg(not(feature = "gpio"))]
+        {
+            println!("Can't use GPIO pin {}, build with --features gpio", pin);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = options.trigger {
+        println!("Clicking for each line read from {}", path);
+        pool.spawn(move |output, inbox| {
+                       line_input::listen(path, "triggers", output, inbox, |_| Some(Input::Trigger))
+                   });
+    }
+    if let Some(path) = options.faces {
+        println!("Pausing when another face is in view, reading face counts from {}", path);
+        pool.spawn(move |output, inbox| face_input::listen(path, output, inbox));
+    }
 
     #[cfg(feature = "viz-2d")]
     let (debug_view, debug_sender) = DebugWindow::new();
     #[cfg(not(feature = "viz-2d"))]
     let debug_sender = DebugSender();
 
-    let handle = thread::spawn(|| run_pipeline(rx, debug_sender));
+    println!("Using profile {}", profile.name);
+    let recorder = CrashRecorder::new(Duration::from_secs(10), &profile);
+    crash_report::install(recorder.clone());
+
+    let handoff = options.handoff.map(|path| match Handoff::listen(&path) {
+        Ok(handoff) => {
+            println!("Handing off clicks to tools connected to {}", path);
+            handoff
+        }
+        Err(e) => {
+            println!("Couldn't listen on {}: {}", path, e);
+            process::exit(1);
+        }
+    });
+
+    let trail = options.trail.map(|path| match TrailLogger::create(&path) {
+        Ok(trail) => {
+            println!("Logging pointer trail to {}", path);
+            trail
+        }
+        Err(e) => {
+            println!("Couldn't create {}: {}", path, e);
+            process::exit(1);
+        }
+    });
+
+    let metrics = Metrics::new();
+    if let Some(ref addr) = options.metrics_addr {
+        match metrics::serve(addr, metrics.clone()) {
+            Ok(()) => println!("Serving metrics on http://{}/metrics", addr),
+            Err(e) => {
+                println!("Couldn't serve metrics on {}: {}", addr, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    signals::install_swap_roles();
+    let handle = thread::spawn(|| {
+        run_pipeline(rx, debug_sender, profile, recorder, metrics, handoff, trail)
+    });
 
     #[cfg(feature = "viz-2d")]
     {
         debug_view.run();
         mem::drop(pool);
     }
+    #[cfg(not(feature = "viz-2d"))]
+    {
+        signals::install_shutdown();
+        signals::wait_for_shutdown();
+        println!("Shutting down");
+        mem::drop(pool);
+    }
 
     handle.join().unwrap();
 }
@@ -0,0 +1,201 @@
+use std::fmt::Write as FmtWrite;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use cgmath::{Vector2, InnerSpace};
+
+use decimation::DecimationStats;
+use secs;
+
+/// Physical effort the user spent moving their head, for therapists to tune
+/// settings that reduce strain.
+pub struct EffortMetrics {
+    /// total head rotation in tracker units, degrees for LinuxTrack
+    pub head_travel: f32,
+    pub jumps: u64,
+    /// head movements between pauses, each one is usually a correction after a jump
+    pub corrections: u64,
+    /// total cursor pixels moved by corrections
+    pub correction_amplitude: f32,
+
+    correcting: bool,
+    amplitude: f32,
+}
+
+impl EffortMetrics {
+    const MOVING_SPEED: f32 = 30.0; // cursor pixels per second that count as moving
+    const MIN_AMPLITUDE: f32 = 2.0; // pixels, smaller movements are tracker noise
+
+    fn new() -> Self {
+        EffortMetrics {
+            head_travel: 0.0,
+            jumps: 0,
+            corrections: 0,
+            correction_amplitude: 0.0,
+            correcting: false,
+            amplitude: 0.0,
+        }
+    }
+
+    pub fn head(&mut self,
+                head_delta: Vector2<f32>,
+                cursor_move: Vector2<f32>,
+                jumped: bool,
+                throwing: bool,
+                dt: f32) {
+        self.head_travel += head_delta.magnitude();
+
+        if jumped {
+            self.jumps += 1;
+        }
+
+        let moving = !throwing && dt > 0.0 && cursor_move.magnitude() / dt > Self::MOVING_SPEED;
+        if moving {
+            if !self.correcting {
+                self.correcting = true;
+                self.amplitude = 0.0;
+            }
+            self.amplitude += cursor_move.magnitude();
+        } else if self.correcting {
+            self.correcting = false;
+            if self.amplitude >= Self::MIN_AMPLITUDE {
+                self.corrections += 1;
+                self.correction_amplitude += self.amplitude;
+            }
+        }
+    }
+
+    fn mean_correction(&self) -> f32 {
+        if self.corrections == 0 {
+            0.0
+        } else {
+            self.correction_amplitude / self.corrections as f32
+        }
+    }
+}
+
+/// Session statistics shared between the pipeline and the metrics endpoint
+pub struct Metrics {
+    start: Instant,
+    pub effort: EffortMetrics,
+    pub decimation: DecimationStats,
+}
+
+pub type SharedMetrics = Arc<Mutex<Metrics>>;
+
+impl Metrics {
+    pub fn new() -> SharedMetrics {
+        Arc::new(Mutex::new(Metrics {
+            start: Instant::now(),
+            effort: EffortMetrics::new(),
+            decimation: DecimationStats::default(),
+        }))
+    }
+
+    fn session_secs(&self) -> f32 {
+        secs(self.start.elapsed())
+    }
+
+    /// Human readable summary for the end of a session
+    pub fn summary(&self) -> String {
+        let minutes = (self.session_secs() / 60.0).max(1.0 / 60.0);
+        let effort = &self.effort;
+        let mut out = String::new();
+        writeln!(out, "Session summary ({:.1} minutes)", minutes).unwrap();
+        writeln!(out,
+                 "  head travel: {:.0} degrees ({:.1} per minute)",
+                 effort.head_travel,
+                 effort.head_travel / minutes)
+                .unwrap();
+        writeln!(out,
+                 "  jumps: {} ({:.1} per minute)",
+                 effort.jumps,
+                 effort.jumps as f32 / minutes)
+                .unwrap();
+        writeln!(out,
+                 "  corrective movements: {} ({:.1} per minute), mean amplitude {:.0}px",
+                 effort.corrections,
+                 effort.corrections as f32 / minutes,
+                 effort.mean_correction())
+                .unwrap();
+        let decimation = &self.decimation;
+        if decimation.overruns > 0 {
+            writeln!(out,
+                     "  fell behind {} times, skipped {} head and {} gaze samples",
+                     decimation.overruns,
+                     decimation.head_dropped,
+                     decimation.gaze_dropped)
+                    .unwrap();
+        }
+        out
+    }
+
+    /// Prometheus text exposition format
+    pub fn exposition(&self) -> String {
+        let effort = &self.effort;
+        let mut out = String::new();
+        {
+            let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+                writeln!(out, "# HELP fusion_mouse_{} {}", name, help).unwrap();
+                writeln!(out, "# TYPE fusion_mouse_{} {}", name, kind).unwrap();
+                writeln!(out, "fusion_mouse_{} {}", name, value).unwrap();
+            };
+            metric("session_seconds",
+                   "gauge",
+                   "Time since the session started.",
+                   self.session_secs() as f64);
+            metric("head_travel_degrees_total",
+                   "counter",
+                   "Cumulative head rotation.",
+                   effort.head_travel as f64);
+            metric("jumps_total", "counter", "Gaze jumps.", effort.jumps as f64);
+            metric("corrections_total",
+                   "counter",
+                   "Corrective head movements.",
+                   effort.corrections as f64);
+            metric("correction_pixels_total",
+                   "counter",
+                   "Cursor distance moved by corrective head movements.",
+                   effort.correction_amplitude as f64);
+            metric("tick_overruns_total",
+                   "counter",
+                   "Samples that took the fusion loop longer than its budget.",
+                   self.decimation.overruns as f64);
+            metric("head_samples_dropped_total",
+                   "counter",
+                   "Stale head samples skipped while falling behind.",
+                   self.decimation.head_dropped as f64);
+            metric("gaze_samples_dropped_total",
+                   "counter",
+                   "Stale gaze samples skipped while falling behind.",
+                   self.decimation.gaze_dropped as f64);
+        }
+        out
+    }
+}
+
+/// Serves metrics over HTTP for scraping by Prometheus or viewing in a browser
+pub fn serve(addr: &str, metrics: SharedMetrics) -> ::std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            // every path gets the metrics, so the request itself doesn't matter
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            let body = metrics.lock().unwrap().exposition();
+            let _ = write!(stream,
+                           "HTTP/1.0 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                            Content-Length: {}\r\n\r\n{}",
+                           body.len(),
+                           body);
+        }
+    });
+    Ok(())
+}
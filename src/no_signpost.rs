@@ -0,0 +1,25 @@
+//! Stands in for the `signpost` crate when built without the `signposts`
+//! feature, so tracing calls compile to nothing.
+#![allow(dead_code)]
+
+pub enum Color {
+    Blue,
+    Green,
+    Purple,
+    Orange,
+    Red,
+}
+
+pub fn trace(_code: u32, _args: &[usize; 4]) {}
+
+pub fn start(_code: u32, _args: &[usize; 4]) {}
+
+pub fn end(_code: u32, _args: &[usize; 4]) {}
+
+pub struct AutoTrace;
+
+impl AutoTrace {
+    pub fn new(_code: u32, _args: &[usize; 4]) -> AutoTrace {
+        AutoTrace
+    }
+}
@@ -0,0 +1,472 @@
+use std::cmp::{min, max};
+use std::mem;
+
+use cgmath::{vec2, Vector2, Vector3, InnerSpace};
+
+use transforms::*;
+use profile::{Profile, FusionRoles, HeadParams, TranslationAction};
+
+/// A head pose from a tracker, with rotation oriented so that positive
+/// movement moves the cursor right and down
+#[derive(Clone, Copy, Debug)]
+pub struct HeadPose {
+    pub rotation: Vector2<f32>,
+    pub translation: Vector3<f32>,
+}
+
+/// What the pipeline did with one head sample
+pub struct HeadUpdate {
+    /// change in smoothed head rotation
+    pub head_delta: Vector2<f32>,
+    /// lines to scroll, fractional
+    pub scroll: f32,
+    pub cursor_move: Vector2<f32>,
+    /// a jump started, even one short enough to land straight away
+    pub jumped: bool,
+    pub dest: Vector2<i32>,
+    /// `dest` confined to the display, where the cursor should go
+    pub confined: Vector2<i32>,
+}
+
+/// The fusion pipeline from raw tracker samples to cursor positions. It doesn't
+/// touch the real cursor or clock so it can also run in simulations.
+pub struct Pipeline {
+    accel: Acceleration,
+    pub params: PolyMouseParams,
+    roles: FusionRoles,
+    /// what `swap_roles` switches to
+    other_roles: FusionRoles,
+    head: HeadParams,
+
+    head_filter: VecOneEuroFilter,
+    warm_up: WarmUp,
+    last_head_pose: Option<Vector2<f32>>,
+    head_origin: Option<Vector2<f32>>,
+    translation_filter: VecOneEuroFilter,
+    lean_filter: OneEuroFilter,
+    last_translation: Option<Vector2<f32>>,
+    lean_origin: Option<f32>,
+
+    pub poly_mouse: PolyMouseTransform,
+    glide: Option<Glide>,
+    puck: Option<PuckTransform>,
+    geometry: Option<GeometryCorrection>,
+
+    fixation_filter: FixationFilter,
+    pub gaze_pt: Vector2<f32>,
+    pub px_gaze: Vector2<f32>,
+    last_gaze_pt: Option<Vector2<f32>>,
+    gaze_fine_move: Vector2<f32>,
+}
+
+impl Pipeline {
+    const MAX_SPEED: f32 = 1.0e6; // pixels per second, far beyond any real movement
+    const WARM_UP_MINCUTOFF: f32 = 1.0; // conservative cutoff right after a reset
+    const MAX_GAP: f32 = 0.5; // seconds without samples that count as a reconnect
+
+    pub fn new(profile: Profile) -> Self {
+        Pipeline {
+            accel: profile.accel,
+            poly_mouse: PolyMouseTransform::new(profile.polymouse.clone()),
+            params: profile.polymouse,
+            roles: profile.roles,
+            other_roles: match profile.roles {
+                FusionRoles::GazeJumps => Profile::head_jumps().roles,
+                FusionRoles::HeadJumps { .. } => FusionRoles::GazeJumps,
+            },
+            head_filter: VecOneEuroFilter::new(profile.head.rotation.mincutoff,
+                                               profile.head.rotation.beta,
+                                               1.0),
+            warm_up: WarmUp::new(0.3, 20),
+            last_head_pose: None,
+            head_origin: None,
+            translation_filter: VecOneEuroFilter::new(profile.head.translation.mincutoff,
+                                                      profile.head.translation.beta,
+                                                      1.0),
+            lean_filter: OneEuroFilter::new(profile.head.translation.mincutoff,
+                                            profile.head.translation.beta,
+                                            1.0),
+            last_translation: None,
+            lean_origin: None,
+            head: profile.head,
+            glide: profile.glide.map(Glide::new),
+            puck: profile.puck.map(PuckTransform::new),
+            geometry: profile.geometry.map(GeometryCorrection::new),
+            fixation_filter: FixationFilter::new(0.03, 150.0),
+            gaze_pt: vec2(0.0, 0.0),
+            px_gaze: vec2(0.0, 0.0),
+            last_gaze_pt: None,
+            gaze_fine_move: vec2(0.0, 0.0),
+        }
+    }
+
+    pub fn head(&mut self,
+                pose: HeadPose,
+                dt: f32,
+                mouse_pt: Vector2<i32>,
+                display: Vector2<f32>)
+                -> HeadUpdate {
+        if dt > Self::MAX_GAP {
+            self.reset_head();
+        }
+        let warm = self.warm_up.update(dt);
+        let rotation_cutoff = Self::warm_up_cutoff(self.head.rotation.mincutoff, warm);
+        self.head_filter.set_mincutoff(rotation_cutoff);
+        let smoothed_head = self.head_filter.filter(pose.rotation, dt);
+        // let smoothed_head = pose.rotation;
+
+        let head_delta = match self.last_head_pose {
+            Some(last_pose) => smoothed_head - last_pose,
+            None => vec2(0.0, 0.0),
+        };
+        self.last_head_pose = Some(smoothed_head);
+
+        let mut move_delta = vec2(0.0, 0.0);
+        if self.head.rotation.enabled {
+            move_delta += head_delta * self.head.rotation.gain;
+        }
+        let scroll = if self.head.translation.enabled {
+            self.translate(pose.translation, warm, dt, &mut move_delta)
+        } else {
+            0.0
+        };
+
+        let mut cursor_move = vec2(self.accel.transform(move_delta.x, dt),
+                                   self.accel.transform(move_delta.y, dt)) * warm;
+        if let Some(ref mut glide) = self.glide {
+            cursor_move = glide.transform(cursor_move, dt);
+        }
+
+        let mouse_pt_f = vec2(mouse_pt.x as f32, mouse_pt.y as f32);
+        let puck_dest = match self.puck {
+            Some(ref mut puck) => {
+                let far_away = self.poly_mouse.looking_far_away(self.gaze_pt, mouse_pt_f);
+                puck.transform(self.gaze_pt, mouse_pt_f, cursor_move, far_away, dt, display)
+            }
+            None => None,
+        };
+
+        let dest = match (puck_dest, self.roles) {
+            (Some(puck_dest), _) => self.poly_mouse.round_move(mouse_pt, puck_dest - mouse_pt_f),
+            (None, FusionRoles::GazeJumps) => {
+                self.poly_mouse.transform(self.gaze_pt, mouse_pt, cursor_move, dt)
+            }
+            (None, FusionRoles::HeadJumps { head_gain, .. }) => {
+                let head_pt = self.head_point(smoothed_head, head_gain, display);
+                let fine_move = mem::replace(&mut self.gaze_fine_move, vec2(0.0, 0.0));
+                self.poly_mouse.transform_split(head_pt, mouse_pt, fine_move, cursor_move, dt)
+            }
+        };
+
+        let jumped = puck_dest.is_none() && self.poly_mouse.jumped();
+
+        let confined = vec2(max(0, min(display.x as i32, dest.x)),
+                            max(0, min(display.y as i32, dest.y)));
+        if confined != dest {
+            // don't keep gliding into the edge of the screen
+            if let Some(ref mut glide) = self.glide {
+                glide.stop();
+            }
+        }
+
+        HeadUpdate {
+            head_delta,
+            scroll,
+            cursor_move,
+            jumped,
+            dest,
+            confined,
+        }
+    }
+
+    /// Where the head points on screen when it sets the jump target, relative
+    /// to where it pointed at the start
+    fn head_point(&mut self,
+                  smoothed_head: Vector2<f32>,
+                  head_gain: f32,
+                  display: Vector2<f32>)
+                  -> Vector2<f32> {
+        let origin = *self.head_origin.get_or_insert(smoothed_head);
+        let rotation = &self.head.rotation;
+        if !rotation.enabled {
+            return display / 2.0;
+        }
+        display / 2.0 + (smoothed_head - origin) * rotation.gain * head_gain
+    }
+
+    /// Handles head translation, adding to `move_delta` or returning lines to scroll
+    fn translate(&mut self,
+                 translation: Vector3<f32>,
+                 warm: f32,
+                 dt: f32,
+                 move_delta: &mut Vector2<f32>)
+                 -> f32 {
+        let cutoff = Self::warm_up_cutoff(self.head.translation.mincutoff, warm);
+        let gain = self.head.translation.gain;
+        match self.head.translation_action {
+            TranslationAction::Move => {
+                self.translation_filter.set_mincutoff(cutoff);
+                let smoothed = self.translation_filter
+                    .filter(vec2(translation.x, translation.y), dt);
+                if let Some(last) = self.last_translation {
+                    *move_delta += (smoothed - last) * gain;
+                }
+                self.last_translation = Some(smoothed);
+                0.0
+            }
+            TranslationAction::Scroll { deadzone, speed } => {
+                self.lean_filter.set_mincutoff(cutoff);
+                let smoothed = self.lean_filter.filter(translation.z, dt);
+                let lean = (smoothed - *self.lean_origin.get_or_insert(smoothed)) * gain;
+                if lean.abs() <= deadzone {
+                    return 0.0;
+                }
+                (lean.abs() - deadzone) * lean.signum() * speed * dt * warm
+            }
+        }
+    }
+
+    /// Filters use a conservative cutoff right after a reset, relaxing to
+    /// `mincutoff` as the warm up progresses
+    fn warm_up_cutoff(mincutoff: f32, warm: f32) -> f32 {
+        let start = Self::WARM_UP_MINCUTOFF.min(mincutoff);
+        start + (mincutoff - start) * warm
+    }
+
+    /// `raw_gaze` is in normalized screen coordinates
+    pub fn gaze(&mut self, raw_gaze: Vector2<f32>, dt: f32, display: Vector2<f32>) {
+        if dt > Self::MAX_GAP {
+            self.reset_gaze();
+        }
+        let screen_gaze = match self.geometry {
+            Some(ref geometry) => geometry.transform(raw_gaze),
+            None => raw_gaze,
+        };
+        self.px_gaze = vec2(screen_gaze.x * display.x, screen_gaze.y * display.y);
+        self.gaze_pt = self.fixation_filter.transform(self.px_gaze, dt);
+
+        if let FusionRoles::HeadJumps { gaze_gain, .. } = self.roles {
+            if let Some(last) = self.last_gaze_pt {
+                let gaze_move = self.gaze_pt - last;
+                // saccades are for looking around, only small movements refine
+                if gaze_move.magnitude() < self.params.min_jump {
+                    self.gaze_fine_move += gaze_move * gaze_gain;
+                }
+            }
+        }
+        self.last_gaze_pt = Some(self.gaze_pt);
+        // println!("GAZE {:?}", self.gaze_pt);
+    }
+
+    /// The precision puck mini-map, if it's open
+    #[cfg(feature = "viz-2d")]
+    pub fn puck(&self) -> Option<Puck> {
+        self.puck.as_ref().and_then(|puck| puck.puck())
+    }
+
+    pub fn roles(&self) -> FusionRoles {
+        self.roles
+    }
+
+    /// Switches between gaze jumps and head jumps, using the `head-jumps`
+    /// profile's gains when the profile didn't set any
+    pub fn swap_roles(&mut self) {
+        mem::swap(&mut self.roles, &mut self.other_roles);
+        self.reset();
+    }
+
+    /// Forgets filter state after tracking was interrupted, like on resume or
+    /// reconnect, so stale reference points don't make the cursor lurch.
+    pub fn reset(&mut self) {
+        self.reset_head();
+        self.reset_gaze();
+    }
+
+    /// Forgets head state after the head tracker lost tracking, leaving gaze alone
+    pub fn reset_head(&mut self) {
+        self.head_filter.reset();
+        self.last_head_pose = None;
+        self.head_origin = None;
+        self.translation_filter.reset();
+        self.lean_filter.reset();
+        self.last_translation = None;
+        self.lean_origin = None;
+        self.poly_mouse.reset();
+        if let Some(ref mut glide) = self.glide {
+            glide.stop();
+        }
+        if let Some(ref mut puck) = self.puck {
+            puck.reset();
+        }
+        self.warm_up.restart();
+        // gathered from gaze but only used up by head samples
+        self.gaze_fine_move = vec2(0.0, 0.0);
+    }
+
+    pub fn reset_gaze(&mut self) {
+        self.fixation_filter.reset();
+        self.last_gaze_pt = None;
+        self.gaze_fine_move = vec2(0.0, 0.0);
+    }
+
+    /// Checks internal state stays bounded, for catching slow leaks and
+    /// runaway accumulators in long simulations.
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let finite = |v: Vector2<f32>| v.x.is_finite() && v.y.is_finite();
+
+        let remainder = self.poly_mouse.rounding_remainder();
+        if remainder.x.abs() >= 1.0 || remainder.y.abs() >= 1.0 {
+            return Err(format!("rounding remainder out of bounds: {:?}", remainder));
+        }
+        let head_speed = self.poly_mouse.smoothed_head_speed();
+        if !head_speed.is_finite() || head_speed > Self::MAX_SPEED {
+            return Err(format!("smoothed head speed out of bounds: {}", head_speed));
+        }
+        if let Some(ref glide) = self.glide {
+            let velocity = glide.velocity();
+            if !finite(velocity) || velocity.magnitude() > Self::MAX_SPEED {
+                return Err(format!("glide velocity out of bounds: {:?}", velocity));
+            }
+        }
+        if self.fixation_filter.buffered() > FixationFilter::MAX_BUFFER {
+            return Err(format!("fixation buffer grew to {}", self.fixation_filter.buffered()));
+        }
+        if !finite(self.gaze_fine_move) ||
+           self.gaze_fine_move.magnitude() > 10.0 * self.params.min_jump {
+            return Err(format!("gaze fine movement piled up: {:?}", self.gaze_fine_move));
+        }
+        if !finite(self.gaze_pt) || !finite(self.px_gaze) {
+            return Err(format!("gaze is not finite: {:?}", self.gaze_pt));
+        }
+        if let Some(pose) = self.last_head_pose {
+            if !finite(pose) {
+                return Err(format!("head pose is not finite: {:?}", pose));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::vec3;
+
+    #[test]
+    fn head_gap_keeps_gaze_state() {
+        let mut pipeline = Pipeline::new(Profile::default());
+        let display = vec2(1920.0, 1080.0);
+        for _ in 0..10 {
+            pipeline.gaze(vec2(0.5, 0.5), 0.01, display);
+        }
+        let pose = HeadPose {
+            rotation: vec2(1.0, 2.0),
+            translation: vec3(0.0, 0.0, 0.0),
+        };
+        pipeline.head(pose, 0.01, vec2(960, 540), display);
+        pipeline.head(pose, 2.0, vec2(960, 540), display);
+        assert!(pipeline.last_head_pose.is_some());
+        assert!(pipeline.last_gaze_pt.is_some());
+
+        pipeline.reset_head();
+        assert!(pipeline.last_head_pose.is_none());
+        assert!(pipeline.last_gaze_pt.is_some());
+        pipeline.reset_gaze();
+        assert!(pipeline.last_gaze_pt.is_none());
+    }
+
+    #[test]
+    fn head_point_follows_rotation_tuning() {
+        let display = vec2(1920.0, 1080.0);
+        let mut pipeline = Pipeline::new(Profile::head_jumps());
+        pipeline.head_point(vec2(0.0, 0.0), 60.0, display);
+        assert_eq!(pipeline.head_point(vec2(2.0, 1.0), 60.0, display), vec2(1080.0, 600.0));
+
+        let mut profile = Profile::head_jumps();
+        profile.head.rotation.gain = -0.5;
+        let mut pipeline = Pipeline::new(profile);
+        pipeline.head_point(vec2(0.0, 0.0), 60.0, display);
+        assert_eq!(pipeline.head_point(vec2(2.0, 1.0), 60.0, display), vec2(900.0, 510.0));
+
+        let mut profile = Profile::head_jumps();
+        profile.head.rotation.enabled = false;
+        let mut pipeline = Pipeline::new(profile);
+        pipeline.head_point(vec2(0.0, 0.0), 60.0, display);
+        assert_eq!(pipeline.head_point(vec2(2.0, 1.0), 60.0, display), vec2(960.0, 540.0));
+    }
+
+    fn translating(action: TranslationAction, gain: f32) -> Pipeline {
+        let mut profile = Profile::head_translation();
+        profile.head.translation.gain = gain;
+        profile.head.translation_action = action;
+        Pipeline::new(profile)
+    }
+
+    #[test]
+    fn translation_moves_with_the_gain_sign() {
+        for &gain in &[2.0, -2.0] {
+            let mut pipeline = translating(TranslationAction::Move, gain);
+            let mut moved = vec2(0.0, 0.0);
+            for i in 0..50 {
+                let mut move_delta = vec2(0.0, 0.0);
+                let scroll = pipeline.translate(vec3(i as f32, 0.0, 0.0), 1.0, 0.01, &mut move_delta);
+                assert_eq!(scroll, 0.0);
+                moved += move_delta;
+            }
+            assert!(moved.x * gain.signum() > 10.0);
+            assert!(moved.y.abs() < 1.0e-3);
+        }
+    }
+
+    #[test]
+    fn leaning_scrolls_past_the_deadzone() {
+        let scroll = TranslationAction::Scroll {
+            deadzone: 20.0,
+            speed: 0.5,
+        };
+        let lines = |pipeline: &mut Pipeline, z: f32| {
+            let mut move_delta = vec2(0.0, 0.0);
+            let mut lines = 0.0;
+            for _ in 0..200 {
+                lines += pipeline.translate(vec3(0.0, 0.0, z), 1.0, 0.01, &mut move_delta);
+            }
+            assert_eq!(move_delta, vec2(0.0, 0.0));
+            lines
+        };
+
+        let mut pipeline = translating(scroll, 1.0);
+        assert_eq!(lines(&mut pipeline, 0.0), 0.0);
+        assert_eq!(lines(&mut pipeline, 15.0), 0.0);
+        assert_eq!(lines(&mut pipeline, -15.0), 0.0);
+        let forward = lines(&mut pipeline, 40.0);
+        // about 20 units past the deadzone for 2 seconds at 0.5 lines per unit
+        assert!(forward > 15.0 && forward < 20.0);
+        assert!(lines(&mut pipeline, -40.0) < -15.0);
+
+        let mut flipped = translating(scroll, -1.0);
+        lines(&mut flipped, 0.0);
+        assert!(lines(&mut flipped, 40.0) < -15.0);
+    }
+
+    #[test]
+    fn head_reset_drops_gaze_fine_move() {
+        let mut pipeline = Pipeline::new(Profile::head_jumps());
+        let display = vec2(1920.0, 1080.0);
+        let pose = HeadPose {
+            rotation: vec2(0.0, 0.0),
+            translation: vec3(0.0, 0.0, 0.0),
+        };
+        let start = pipeline.head(pose, 0.01, vec2(960, 540), display).confined;
+
+        // gaze keeps drifting while the head tracker is lost
+        for i in 0..100 {
+            pipeline.gaze(vec2(0.5 + i as f32 * 0.001, 0.5), 0.01, display);
+        }
+        assert!(pipeline.gaze_fine_move.x > 0.0);
+        pipeline.reset_head();
+        assert_eq!(pipeline.gaze_fine_move, vec2(0.0, 0.0));
+
+        let update = pipeline.head(pose, 0.01, start, display);
+        assert!((update.confined.x - start.x).abs() <= 1);
+    }
+}
@@ -0,0 +1,138 @@
+use cgmath::{Vector2, MetricSpace};
+
+#[derive(Clone, Debug)]
+pub struct PrivacyParams {
+    pub confirm_time: f32, // seconds of looking at the cursor needed to resume
+    pub confirm_radius: f32, // pixels around the cursor that count as looking at it
+}
+
+/// Pauses output while a webcam backend sees more than one face, so someone
+/// leaning in can't take over the cursor. Only the primary user can resume,
+/// by looking at the frozen cursor once they're alone again.
+pub struct PrivacyGuard {
+    params: PrivacyParams,
+    faces: u32,
+    paused: bool,
+    confirmed_for: f32,
+}
+
+impl PrivacyGuard {
+    pub fn new(params: PrivacyParams) -> Self {
+        PrivacyGuard {
+            params,
+            faces: 1,
+            paused: false,
+            confirmed_for: 0.0,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn faces(&mut self, count: u32) {
+        self.faces = count;
+        if count > 1 {
+            if !self.paused {
+                println!("{} faces in view, pausing until you look at the cursor", count);
+            }
+            self.paused = true;
+            self.confirmed_for = 0.0;
+        }
+    }
+
+    /// Returns true when this gaze sample resumed output
+    pub fn gaze(&mut self, gaze_pt: Vector2<f32>, cursor: Vector2<f32>, dt: f32) -> bool {
+        if !self.paused {
+            return false;
+        }
+        if self.faces != 1 || gaze_pt.distance(cursor) > self.params.confirm_radius {
+            self.confirmed_for = 0.0;
+            return false;
+        }
+        self.confirmed_for += dt;
+        if self.confirmed_for >= self.params.confirm_time {
+            println!("Resuming");
+            self.paused = false;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::vec2;
+
+    fn guard() -> PrivacyGuard {
+        PrivacyGuard::new(PrivacyParams {
+            confirm_time: 1.0,
+            confirm_radius: 60.0,
+        })
+    }
+
+    #[test]
+    fn one_face_never_pauses() {
+        let mut guard = guard();
+        guard.faces(1);
+        guard.faces(0);
+        assert!(!guard.is_paused());
+        assert!(!guard.gaze(vec2(500.0, 500.0), vec2(0.0, 0.0), 0.1));
+    }
+
+    #[test]
+    fn second_face_pauses_until_confirmed() {
+        let mut guard = guard();
+        let cursor = vec2(400.0, 300.0);
+        guard.faces(2);
+        assert!(guard.is_paused());
+
+        // looking at the cursor doesn't count while someone else is in view
+        for _ in 0..20 {
+            assert!(!guard.gaze(cursor, cursor, 0.1));
+        }
+        assert!(guard.is_paused());
+
+        guard.faces(1);
+        for _ in 0..9 {
+            assert!(!guard.gaze(cursor + vec2(30.0, 0.0), cursor, 0.1));
+        }
+        assert!(guard.is_paused());
+        assert!(guard.gaze(cursor, cursor, 0.15));
+        assert!(!guard.is_paused());
+    }
+
+    #[test]
+    fn looking_away_restarts_confirmation() {
+        let mut guard = guard();
+        let cursor = vec2(400.0, 300.0);
+        guard.faces(2);
+        guard.faces(1);
+        for _ in 0..8 {
+            guard.gaze(cursor, cursor, 0.1);
+        }
+        assert!(!guard.gaze(cursor + vec2(100.0, 0.0), cursor, 0.1));
+        for _ in 0..8 {
+            assert!(!guard.gaze(cursor, cursor, 0.1));
+        }
+        assert!(guard.is_paused());
+    }
+
+    #[test]
+    fn another_face_during_confirmation_restarts_it() {
+        let mut guard = guard();
+        let cursor = vec2(400.0, 300.0);
+        guard.faces(2);
+        guard.faces(1);
+        for _ in 0..8 {
+            guard.gaze(cursor, cursor, 0.1);
+        }
+        guard.faces(2);
+        guard.faces(1);
+        for _ in 0..8 {
+            assert!(!guard.gaze(cursor, cursor, 0.1));
+        }
+        assert!(guard.gaze(cursor, cursor, 0.25));
+    }
+}
@@ -0,0 +1,200 @@
+use transforms::*;
+use privacy::PrivacyParams;
+use click_guard::ClickConfirmParams;
+use decimation::DecimationParams;
+
+/// Which input sets the coarse jump target and which does fine adjustment
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FusionRoles {
+    /// the usual PolyMouse setup, gaze jumps and the head refines
+    GazeJumps,
+    /// for users whose head tracking is better than their gaze tracking, the
+    /// head points absolutely and small gaze movements refine
+    HeadJumps {
+        head_gain: f32, // pixels per head unit from the starting pose
+        gaze_gain: f32, // fraction of small gaze movements applied to the cursor
+    },
+}
+
+/// Tuning for one component of a 6DOF head pose
+#[derive(Clone, Debug)]
+pub struct HeadComponent {
+    pub enabled: bool,
+    pub gain: f32, // multiplies tracker units, negative flips the direction
+    pub mincutoff: f32, // one euro filter minimum cutoff
+    pub beta: f32, // one euro filter speed coefficient
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TranslationAction {
+    /// sideways and vertical head translation moves the cursor like rotation
+    Move,
+    /// leaning towards or away from the screen scrolls
+    Scroll {
+        deadzone: f32, // tracker units of lean that don't scroll
+        speed: f32, // lines per second per tracker unit past the deadzone
+    },
+}
+
+/// How rotation and translation of the head are used, handled separately so
+/// e.g. rotation can move the cursor while leaning scrolls.
+#[derive(Clone, Debug)]
+pub struct HeadParams {
+    pub rotation: HeadComponent,
+    pub translation: HeadComponent,
+    pub translation_action: TranslationAction,
+}
+
+/// A named set of tuning parameters for the pipeline
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub name: &'static str,
+    pub roles: FusionRoles,
+    pub accel: Acceleration,
+    pub polymouse: PolyMouseParams,
+    pub head: HeadParams,
+    /// momentum after quick head flicks, disabled when `None`
+    pub glide: Option<GlideParams>,
+    /// correction for curved screens and projectors, linear mapping when `None`
+    pub geometry: Option<GeometryParams>,
+    /// pause when a webcam backend sees a second face, never pauses when `None`
+    pub privacy: Option<PrivacyParams>,
+    /// mini-map navigation for users who can't make fast movements, off when `None`
+    pub puck: Option<PuckParams>,
+    /// screen regions where clicks need a second trigger
+    pub click_confirm: ClickConfirmParams,
+    /// skipping stale samples when the fusion loop falls behind, never skips when `None`
+    pub decimation: Option<DecimationParams>,
+}
+
+impl Profile {
+    pub fn names() -> &'static [&'static str] {
+        &["default", "head-jumps", "puck", "glide", "head-translation"]
+    }
+
+    pub fn by_name(name: &str) -> Option<Profile> {
+        match name {
+            "default" => Some(Profile::default()),
+            "head-jumps" => Some(Profile::head_jumps()),
+            "puck" => Some(Profile::puck()),
+            "glide" => Some(Profile::glide()),
+            "head-translation" => Some(Profile::head_translation()),
+            _ => None,
+        }
+    }
+
+    pub fn head_jumps() -> Profile {
+        Profile {
+            name: "head-jumps",
+            roles: FusionRoles::HeadJumps {
+                head_gain: 60.0,
+                gaze_gain: 0.3,
+            },
+            ..Profile::default()
+        }
+    }
+
+    pub fn puck() -> Profile {
+        Profile {
+            name: "puck",
+            puck: Some(PuckParams {
+                scale: 0.15,
+                open_time: 0.6,
+                close_time: 0.8,
+                still_speed: 60.0, // pixels per second
+            }),
+            ..Profile::default()
+        }
+    }
+
+    /// for users who find moving their head sideways easier than turning it
+    pub fn head_translation() -> Profile {
+        let mut head = Profile::default().head;
+        head.translation.enabled = true;
+        head.translation_action = TranslationAction::Move;
+        Profile {
+            name: "head-translation",
+            head,
+            ..Profile::default()
+        }
+    }
+
+    pub fn glide() -> Profile {
+        Profile {
+            name: "glide",
+            glide: Some(GlideParams {
+                flick_speed: 2500.0, // pixels per second
+                friction: 4.0, // velocity decay per second
+                min_speed: 40.0, // pixels per second
+                brake_speed: 200.0, // pixels per second
+            }),
+            ..Profile::default()
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Profile {
+        Profile {
+            name: "default",
+            roles: FusionRoles::GazeJumps,
+            accel: Acceleration {
+                cd_min: 8.0, // min gain
+                cd_max: 65.0, // max gain
+                v_min: 0.0004, // input velocity lower bound
+                v_max: 0.0025, // input velocity upper bound
+                lambda: 1000.0, // slope of curve at inflection point
+                ratio: 0.7, // where inflection lies between v_min and v_max
+            },
+            polymouse: PolyMouseParams {
+                min_jump: 100.0,
+                speed_expand_factor: 0.0, // TODO translate delta->speed
+                head_smoothing_factor: 1.0, // TODO tune for dt
+                throw_thresh_speed: 300.0, // pixels per second
+                throw_speed: 8000.0, // pixels per second
+                small_jump_factor: 0.75,
+                rounding: Rounding::Accumulate,
+            },
+            head: HeadParams {
+                rotation: HeadComponent {
+                    enabled: true,
+                    gain: 1.0,
+                    mincutoff: 6.0,
+                    beta: 1000.0,
+                },
+                translation: HeadComponent {
+                    enabled: false,
+                    gain: 1.0,
+                    mincutoff: 1.0,
+                    beta: 10.0,
+                },
+                translation_action: TranslationAction::Scroll {
+                    deadzone: 20.0,
+                    speed: 0.5,
+                },
+            },
+            glide: None,
+            geometry: None,
+            // geometry: Some(GeometryParams {
+            //     keystone: None,
+            //     curve_angle: 0.9, // radians, about a 1800R 34" ultrawide
+            //     mesh: None,
+            // }),
+            privacy: None,
+            // privacy: Some(PrivacyParams {
+            //     confirm_time: 1.0,
+            //     confirm_radius: 60.0,
+            // }),
+            puck: None,
+            click_confirm: ClickConfirmParams {
+                regions: vec![],
+                confirm_time: 2.0,
+                max_drift: 20,
+            },
+            decimation: Some(DecimationParams {
+                tick_budget: 0.004, // 120Hz head and 90Hz gaze leave under 5ms per sample
+                hold_time: 1.0,
+            }),
+        }
+    }
+}
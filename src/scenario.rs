@@ -0,0 +1,686 @@
+//! Behavioural tests for the pipeline written as data instead of Rust.
+//!
+//! A scenario describes a timeline of gaze and head input and the outcomes
+//! expected from it, in a small subset of YAML: top level `key: value` lines,
+//! flow style `[lists]` and `{maps}`, block lists of `- ` items and `#`
+//! comments, which need whitespace before them and don't count inside quotes.
+//!
+//! ```yaml
+//! name: jump to the right
+//! profile: default      # any `--profile` name
+//! rate: 120             # samples per second
+//! duration: 2.0         # seconds
+//! display: [1920, 1080]
+//! cursor: [960, 540]    # starting position in pixels
+//! gaze_noise: 0.005     # standard deviation in normalized units, 0 by default
+//! seed: 1
+//! inputs:
+//!   # gaze in normalized screen coordinates, holds until the next gaze value
+//!   # head yaw and pitch in degrees, positive turns right and down,
+//!   # interpolated linearly between head values
+//!   - {t: 0.0, gaze: [0.5, 0.5], head: [0, 0]}
+//!   - {t: 1.0, gaze: [0.85, 0.5]}
+//!   - {t: 1.2, head: [0, 0]}
+//!   - {t: 1.3, head: [2, 0]}
+//! expect:
+//!   # jumps starting between `from` and `to`, each landing within `within`
+//!   # pixels of `near` if given
+//!   - {jumps: 1, from: 1.2, to: 1.4, near: [1532, 540], within: 30}
+//!   # where the cursor is at time `at`
+//!   - {at: 2.0, near: [1560, 540], within: 40}
+//! ```
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use cgmath::{vec2, vec3, Vector2, MetricSpace};
+
+use pipeline::{Pipeline, HeadPose};
+use profile::Profile;
+use synthetic_input::XorShift;
+
+#[derive(Debug)]
+enum Value {
+    Scalar(String),
+    List(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn num(&self) -> Result<f32, String> {
+        match *self {
+            Value::Scalar(ref s) => s.parse().map_err(|_| format!("expected a number, got `{}`", s)),
+            _ => Err("expected a number".to_owned()),
+        }
+    }
+
+    fn text(&self) -> Result<&str, String> {
+        match *self {
+            Value::Scalar(ref s) => Ok(s),
+            _ => Err("expected text".to_owned()),
+        }
+    }
+
+    fn point(&self) -> Result<Vector2<f32>, String> {
+        match *self {
+            Value::List(ref items) if items.len() == 2 => Ok(vec2(items[0].num()?, items[1].num()?)),
+            _ => Err("expected [x, y]".to_owned()),
+        }
+    }
+
+    /// Looks up the keys of a map, failing on any key not in `allowed`
+    fn fields<'a>(&'a self, allowed: &[&str]) -> Result<Fields<'a>, String> {
+        match *self {
+            Value::Map(ref entries) => {
+                for &(ref key, _) in entries {
+                    if !allowed.contains(&key.as_str()) {
+                        return Err(format!("unknown key `{}`, expected one of: {}",
+                                           key,
+                                           allowed.join(", ")));
+                    }
+                }
+                Ok(Fields { entries })
+            }
+            _ => Err("expected a {map}".to_owned()),
+        }
+    }
+}
+
+struct Fields<'a> {
+    entries: &'a [(String, Value)],
+}
+
+impl<'a> Fields<'a> {
+    fn get(&self, key: &str) -> Option<&'a Value> {
+        self.entries.iter().find(|entry| entry.0 == key).map(|entry| &entry.1)
+    }
+
+    fn num(&self, key: &str) -> Result<Option<f32>, String> {
+        match self.get(key) {
+            Some(value) => value.num().map(Some).map_err(|e| format!("{}: {}", key, e)),
+            None => Ok(None),
+        }
+    }
+
+    fn point(&self, key: &str) -> Result<Option<Vector2<f32>>, String> {
+        match self.get(key) {
+            Some(value) => value.point().map(Some).map_err(|e| format!("{}: {}", key, e)),
+            None => Ok(None),
+        }
+    }
+
+    fn require<T>(&self, key: &str, value: Option<T>) -> Result<T, String> {
+        value.ok_or_else(|| format!("missing `{}`", key))
+    }
+}
+
+fn skip_spaces(chars: &[char], pos: &mut usize) {
+    while *pos < chars.len() && chars[*pos].is_whitespace() {
+        *pos += 1;
+    }
+}
+
+fn unquote(s: &str) -> String {
+    let s = s.trim();
+    let quoted = |q: char| s.len() >= 2 && s.starts_with(q) && s.ends_with(q);
+    if quoted('"') || quoted('\'') {
+        s[1..s.len() - 1].to_owned()
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Reads up to the next unnested character in `ends`
+fn parse_scalar(chars: &[char], pos: &mut usize, ends: &[char]) -> Result<String, String> {
+    let start = *pos;
+    while *pos < chars.len() && !ends.contains(&chars[*pos]) {
+        *pos += 1;
+    }
+    let scalar = unquote(&chars[start..*pos].iter().collect::<String>());
+    if scalar.is_empty() {
+        return Err("missing value".to_owned());
+    }
+    Ok(scalar)
+}
+
+fn parse_flow(chars: &[char], pos: &mut usize) -> Result<Value, String> {
+    skip_spaces(chars, pos);
+    match chars.get(*pos) {
+        Some(&'[') => {
+            *pos += 1;
+            let mut items = vec![];
+            loop {
+                skip_spaces(chars, pos);
+                if chars.get(*pos) == Some(&']') {
+                    *pos += 1;
+                    return Ok(Value::List(items));
+                }
+                items.push(parse_flow(chars, pos)?);
+                skip_spaces(chars, pos);
+                match chars.get(*pos) {
+                    Some(&',') => *pos += 1,
+                    Some(&']') => (),
+                    _ => return Err("expected `,` or `]`".to_owned()),
+                }
+            }
+        }
+        Some(&'{') => {
+            *pos += 1;
+            let mut entries = vec![];
+            loop {
+                skip_spaces(chars, pos);
+                if chars.get(*pos) == Some(&'}') {
+                    *pos += 1;
+                    return Ok(Value::Map(entries));
+                }
+                let key = parse_scalar(chars, pos, &[':', ',', '}'])?;
+                if chars.get(*pos) != Some(&':') {
+                    return Err(format!("expected `:` after `{}`", key));
+                }
+                *pos += 1;
+                entries.push((key, parse_flow(chars, pos)?));
+                skip_spaces(chars, pos);
+                match chars.get(*pos) {
+                    Some(&',') => *pos += 1,
+                    Some(&'}') => (),
+                    _ => return Err("expected `,` or `}`".to_owned()),
+                }
+            }
+        }
+        _ => parse_scalar(chars, pos, &[',', ']', '}']).map(Value::Scalar),
+    }
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    let text = text.trim();
+    if !text.starts_with('[') && !text.starts_with('{') {
+        // plain text may contain commas, like a name
+        return Ok(Value::Scalar(unquote(text)));
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let mut pos = 0;
+    let value = parse_flow(&chars, &mut pos)?;
+    skip_spaces(&chars, &mut pos);
+    if pos < chars.len() {
+        return Err(format!("unexpected `{}`", chars[pos..].iter().collect::<String>()));
+    }
+    Ok(value)
+}
+
+/// Cuts off a comment, which like in YAML starts at a `#` at the start of the
+/// line or after whitespace, outside of quoted values
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (index, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            // quotes only count at the start of a value, so `don't` is plain
+            None if (c == '"' || c == '\'') && (prev.is_whitespace() || "[{,:".contains(prev)) => {
+                quote = Some(c)
+            }
+            None if c == '#' && prev.is_whitespace() => return &line[..index],
+            None => (),
+        }
+        prev = c;
+    }
+    line
+}
+
+/// A top level `key: value`, or `key:` followed by `- item` lines
+struct Entry {
+    line: usize,
+    key: String,
+    value: Option<Value>,
+    items: Vec<(usize, Value)>,
+}
+
+fn parse_document(text: &str) -> Result<Vec<Entry>, String> {
+    let mut entries: Vec<Entry> = vec![];
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = index + 1;
+        let content = strip_comment(raw_line);
+        let trimmed = content.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        if trimmed.starts_with('-') {
+            let item = parse_value(&trimmed[1..]).map_err(|e| format!("line {}: {}", line, e))?;
+            if !entries.last().map_or(false, |entry| entry.value.is_none()) {
+                return Err(format!("line {}: list item outside of a list", line));
+            }
+            entries.last_mut().unwrap().items.push((line, item));
+            continue;
+        }
+        if content.starts_with(char::is_whitespace) {
+            return Err(format!("line {}: only list items may be indented", line));
+        }
+
+        let colon = match trimmed.find(':') {
+            Some(colon) => colon,
+            None => return Err(format!("line {}: expected `key: value`", line)),
+        };
+        let rest = trimmed[colon + 1..].trim();
+        let value = if rest.is_empty() {
+            None
+        } else {
+            Some(parse_value(rest).map_err(|e| format!("line {}: {}", line, e))?)
+        };
+        entries.push(Entry {
+                         line,
+                         key: trimmed[..colon].trim().to_owned(),
+                         value,
+                         items: vec![],
+                     });
+    }
+    Ok(entries)
+}
+
+enum Expectation {
+    /// `count` jumps start between `from` and `to`, each landing within
+    /// `within` pixels of `near` if given
+    Jumps {
+        count: usize,
+        from: f32,
+        to: f32,
+        landing: Option<(Vector2<f32>, f32)>,
+    },
+    /// the cursor is within `within` pixels of `near` at time `at`
+    Cursor {
+        at: f32,
+        near: Vector2<f32>,
+        within: f32,
+    },
+}
+
+impl Expectation {
+    fn parse(value: &Value) -> Result<Expectation, String> {
+        if let Value::Map(ref entries) = *value {
+            if entries.iter().any(|entry| entry.0 == "jumps") {
+                let fields = value.fields(&["jumps", "from", "to", "near", "within"])?;
+                let count = fields.require("jumps", fields.num("jumps")?)?;
+                if count < 0.0 || count.fract() != 0.0 {
+                    return Err(format!("`jumps` must be a whole number, got {}", count));
+                }
+                let landing = match (fields.point("near")?, fields.num("within")?) {
+                    (Some(near), Some(within)) => Some((near, within)),
+                    (None, None) => None,
+                    _ => return Err("`near` and `within` go together".to_owned()),
+                };
+                return Ok(Expectation::Jumps {
+                              count: count as usize,
+                              from: fields.num("from")?.unwrap_or(0.0),
+                              to: fields.num("to")?.unwrap_or(::std::f32::INFINITY),
+                              landing,
+                          });
+            }
+        }
+        let fields = value.fields(&["at", "near", "within"])?;
+        Ok(Expectation::Cursor {
+               at: fields.require("at", fields.num("at")?)?,
+               near: fields.require("near", fields.point("near")?)?,
+               within: fields.require("within", fields.num("within")?)?,
+           })
+    }
+}
+
+struct Jump {
+    time: f32,
+    landing: Option<Vector2<i32>>,
+}
+
+pub struct Scenario {
+    pub name: String,
+    profile: Profile,
+    rate: f32,
+    duration: f32,
+    display: Vector2<f32>,
+    cursor: Vector2<i32>,
+    gaze_noise: f32,
+    seed: u32,
+    gaze: Vec<(f32, Vector2<f32>)>,
+    head: Vec<(f32, Vector2<f32>)>,
+    expectations: Vec<(usize, Expectation)>,
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Scenario, String> {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| e.to_string())?;
+        let name = Path::new(path)
+            .file_stem()
+            .map_or(path.to_owned(), |stem| stem.to_string_lossy().into_owned());
+        Scenario::parse(name, &text)
+    }
+
+    pub fn parse(name: String, text: &str) -> Result<Scenario, String> {
+        let mut scenario = Scenario {
+            name,
+            profile: Profile::default(),
+            rate: 120.0,
+            duration: 0.0,
+            display: vec2(1920.0, 1080.0),
+            cursor: vec2(960, 540),
+            gaze_noise: 0.0,
+            seed: 1,
+            gaze: vec![],
+            head: vec![],
+            expectations: vec![],
+        };
+        for entry in parse_document(text)? {
+            scenario.apply(&entry)?;
+        }
+        if scenario.duration <= 0.0 {
+            return Err("missing a positive `duration`".to_owned());
+        }
+        if scenario.rate <= 0.0 {
+            return Err("`rate` must be positive".to_owned());
+        }
+        for &(line, ref expectation) in &scenario.expectations {
+            if let Expectation::Cursor { at, .. } = *expectation {
+                if at > scenario.duration {
+                    return Err(format!("line {}: `at` {}s is after the end at {}s",
+                                       line,
+                                       at,
+                                       scenario.duration));
+                }
+            }
+        }
+        Ok(scenario)
+    }
+
+    fn apply(&mut self, entry: &Entry) -> Result<(), String> {
+        let at_line = |line: usize| move |e: String| format!("line {}: {}", line, e);
+        let value = match entry.value {
+            Some(ref value) => value,
+            None => {
+                return match entry.key.as_str() {
+                           "inputs" => {
+                               for &(line, ref item) in &entry.items {
+                                   self.add_input(item).map_err(at_line(line))?;
+                               }
+                               Ok(())
+                           }
+                           "expect" => {
+                               for &(line, ref item) in &entry.items {
+                                   let expectation = Expectation::parse(item).map_err(at_line(line))?;
+                                   self.expectations.push((line, expectation));
+                               }
+                               Ok(())
+                           }
+                           key => Err(format!("line {}: `{}` needs a value", entry.line, key)),
+                       };
+            }
+        };
+        let res = match entry.key.as_str() {
+            "name" => value.text().map(|name| self.name = name.to_owned()),
+            "profile" => {
+                value
+                    .text()
+                    .and_then(|name| {
+                        Profile::by_name(name).ok_or_else(|| {
+                            format!("unknown profile {}, choose one of: {}",
+                                    name,
+                                    Profile::names().join(", "))
+                        })
+                    })
+                    .map(|profile| self.profile = profile)
+            }
+            "rate" => value.num().map(|rate| self.rate = rate),
+            "duration" => value.num().map(|duration| self.duration = duration),
+            "display" => value.point().map(|display| self.display = display),
+            "cursor" => value.point().map(|cursor| self.cursor = vec2(cursor.x as i32, cursor.y as i32)),
+            "gaze_noise" => value.num().map(|noise| self.gaze_noise = noise),
+            "seed" => value.num().map(|seed| self.seed = seed as u32),
+            key => Err(format!("unknown key `{}`", key)),
+        };
+        res.map_err(at_line(entry.line))
+    }
+
+    fn add_input(&mut self, item: &Value) -> Result<(), String> {
+        let fields = item.fields(&["t", "gaze", "head"])?;
+        let t = fields.require("t", fields.num("t")?)?;
+        let last_t = self.gaze.iter().chain(self.head.iter()).map(|frame| frame.0).fold(0.0, f32::max);
+        if t < last_t {
+            return Err(format!("inputs must be in time order, {} comes after {}", t, last_t));
+        }
+        if let Some(gaze) = fields.point("gaze")? {
+            self.gaze.push((t, gaze));
+        }
+        if let Some(head) = fields.point("head")? {
+            self.head.push((t, head));
+        }
+        Ok(())
+    }
+
+    /// Gaze holds each value until the next, like the eyes jumping between fixations
+    fn gaze_at(&self, t: f32) -> Option<Vector2<f32>> {
+        self.gaze
+            .iter()
+            .rev()
+            .find(|frame| frame.0 <= t)
+            .or(self.gaze.first())
+            .map(|frame| frame.1)
+    }
+
+    /// Head moves linearly between values
+    fn head_at(&self, t: f32) -> Option<Vector2<f32>> {
+        let next = match self.head.iter().position(|frame| frame.0 > t) {
+            Some(0) => return self.head.first().map(|frame| frame.1),
+            Some(next) => next,
+            None => return self.head.last().map(|frame| frame.1),
+        };
+        let (t0, p0) = self.head[next - 1];
+        let (t1, p1) = self.head[next];
+        Some(p0 + (p1 - p0) * ((t - t0) / (t1 - t0)))
+    }
+
+    /// Runs the scenario, returning the expectations that weren't met
+    pub fn run(&self) -> Vec<String> {
+        let dt = 1.0 / self.rate;
+        let ticks = (self.duration * self.rate).round() as usize;
+        let mut pipeline = Pipeline::new(self.profile.clone());
+        let mut rng = XorShift::new(self.seed);
+        let mut cursor = self.cursor;
+        let mut failures = vec![];
+
+        // cursor position at each tick, starting from time 0
+        let mut trace = Vec::with_capacity(ticks + 1);
+        trace.push(cursor);
+        let mut jumps: Vec<Jump> = vec![];
+        let mut last_dest = pipeline.poly_mouse.last_jump_destination;
+
+        for tick in 1..ticks + 1 {
+            let time = tick as f32 * dt;
+            let cursor_f = vec2(cursor.x as f32, cursor.y as f32);
+            let mut gaze = self.gaze_at(time)
+                .unwrap_or(vec2(cursor_f.x / self.display.x, cursor_f.y / self.display.y));
+            if self.gaze_noise > 0.0 {
+                gaze += vec2(rng.next_gaussian(), rng.next_gaussian()) * self.gaze_noise;
+            }
+            pipeline.gaze(gaze, dt, self.display);
+            let pose = HeadPose {
+                rotation: self.head_at(time).unwrap_or(vec2(0.0, 0.0)),
+                translation: vec3(0.0, 0.0, 0.0),
+            };
+            let update = pipeline.head(pose, dt, cursor, self.display);
+            cursor = update.confined;
+            trace.push(cursor);
+
+            let dest = pipeline.poly_mouse.last_jump_destination;
+            let landed = dest != last_dest;
+            last_dest = dest;
+            if update.jumped {
+                jumps.push(Jump {
+                               time,
+                               landing: None,
+                           });
+            }
+            if landed {
+                if let Some(jump) = jumps.last_mut() {
+                    jump.landing = Some(cursor);
+                }
+            }
+
+            if let Err(msg) = pipeline.check_invariants() {
+                failures.push(format!("{:.3}s: invariant violated: {}", time, msg));
+                return failures;
+            }
+        }
+
+        for &(line, ref expectation) in &self.expectations {
+            match *expectation {
+                Expectation::Jumps { count, from, to, landing } => {
+                    let matching: Vec<&Jump> =
+                        jumps.iter().filter(|jump| jump.time >= from && jump.time <= to).collect();
+                    if matching.len() != count {
+                        let times: Vec<String> =
+                            jumps.iter().map(|jump| format!("{:.3}s", jump.time)).collect();
+                        failures.push(format!("line {}: expected {} jumps between {}s and {}s, got {} \
+                                               (all jumps: {})",
+                                              line,
+                                              count,
+                                              from,
+                                              to,
+                                              matching.len(),
+                                              if times.is_empty() { "none".to_owned() } else { times.join(", ") }));
+                        continue;
+                    }
+                    if let Some((near, within)) = landing {
+                        for jump in matching {
+                            match jump.landing {
+                                Some(pt) => {
+                                    let dist = vec2(pt.x as f32, pt.y as f32).distance(near);
+                                    if dist > within {
+                                        failures.push(format!("line {}: jump at {:.3}s landed at ({}, {}), \
+                                                               {:.0}px from ({}, {}), expected within {}",
+                                                              line,
+                                                              jump.time,
+                                                              pt.x,
+                                                              pt.y,
+                                                              dist,
+                                                              near.x,
+                                                              near.y,
+                                                              within));
+                                    }
+                                }
+                                None => {
+                                    failures.push(format!("line {}: jump at {:.3}s never landed",
+                                                          line,
+                                                          jump.time))
+                                }
+                            }
+                        }
+                    }
+                }
+                Expectation::Cursor { at, near, within } => {
+                    let index = ((at * self.rate).round() as usize).min(trace.len() - 1);
+                    let pt = trace[index];
+                    let dist = vec2(pt.x as f32, pt.y as f32).distance(near);
+                    if dist > within {
+                        failures.push(format!("line {}: cursor at {}s was at ({}, {}), {:.0}px from \
+                                               ({}, {}), expected within {}",
+                                              line,
+                                              at,
+                                              pt.x,
+                                              pt.y,
+                                              dist,
+                                              near.x,
+                                              near.y,
+                                              within));
+                    }
+                }
+            }
+        }
+        failures
+    }
+}
+
+/// Loads and runs scenario files, printing the results. Returns whether all passed.
+pub fn run_files(paths: &[String]) -> bool {
+    let mut passed = 0;
+    for path in paths {
+        let failures = match Scenario::load(path) {
+            Ok(scenario) => {
+                let failures = scenario.run();
+                if failures.is_empty() {
+                    println!("PASS {} ({})", scenario.name, path);
+                } else {
+                    println!("FAIL {} ({})", scenario.name, path);
+                }
+                failures
+            }
+            Err(e) => {
+                println!("FAIL {}", path);
+                vec![e]
+            }
+        };
+        for failure in &failures {
+            println!("  {}", failure);
+        }
+        if failures.is_empty() {
+            passed += 1;
+        }
+    }
+    println!("{} of {} scenarios passed", passed, paths.len());
+    passed == paths.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn strips_comments_outside_quotes() {
+        assert_eq!(strip_comment("# a comment"), "");
+        assert_eq!(strip_comment("rate: 120 # per second"), "rate: 120 ");
+        assert_eq!(strip_comment("name: \"issue #12\" # why"), "name: \"issue #12\" ");
+        assert_eq!(strip_comment("name: 'issue #12'"), "name: 'issue #12'");
+        assert_eq!(strip_comment("- {name: \"a # b\", t: 1}"), "- {name: \"a # b\", t: 1}");
+        assert_eq!(strip_comment("name: C#"), "name: C#");
+        assert_eq!(strip_comment("name: don't # comment"), "name: don't ");
+    }
+
+    #[test]
+    fn quoted_hash_is_part_of_the_name() {
+        let text = "name: \"issue #12\" # regression\nduration: 1.0\n";
+        let scenario = Scenario::parse("unnamed".to_owned(), text).unwrap();
+        assert_eq!(scenario.name, "issue #12");
+    }
+
+    #[test]
+    fn rejects_fractional_or_negative_jumps() {
+        for count in &["-1", "1.5"] {
+            let text = format!("duration: 1.0\nexpect:\n  - {{jumps: {}}}\n", count);
+            let err = Scenario::parse("unnamed".to_owned(), &text).err().unwrap();
+            assert!(err.starts_with("line 3: `jumps`"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn rejects_cursor_check_after_the_end() {
+        let text = "duration: 1.0\nexpect:\n  - {at: 1.5, near: [0, 0], within: 10}\n";
+        let err = Scenario::parse("unnamed".to_owned(), text).err().unwrap();
+        assert!(err.starts_with("line 3: `at`"), "{}", err);
+    }
+
+    #[test]
+    fn example_scenarios_pass() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios");
+        let mut paths: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "yaml"))
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+        assert!(run_files(&paths));
+    }
+}
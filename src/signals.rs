@@ -0,0 +1,60 @@
+//! Unix signals for controlling a running FusionMouse without a socket.
+//!
+//! Ctrl-c and SIGTERM shut down cleanly, so input threads get to stop and the
+//! session summary still gets printed. A second one exits straight away in
+//! case shutting down gets stuck. SIGUSR1 swaps the roles of the trackers.
+
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+const SIGINT: c_int = 2;
+const SIGTERM: c_int = 15;
+#[cfg(target_os = "macos")]
+const SIGUSR1: c_int = 30;
+#[cfg(not(target_os = "macos"))]
+const SIGUSR1: c_int = 10;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static SWAP_ROLES: AtomicBool = AtomicBool::new(false);
+
+extern "C" {
+    fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    fn _exit(status: c_int) -> !;
+}
+
+extern "C" fn handle_shutdown(signum: c_int) {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        unsafe { _exit(128 + signum) }
+    }
+}
+
+extern "C" fn handle_swap_roles(_: c_int) {
+    SWAP_ROLES.store(true, Ordering::SeqCst);
+}
+
+pub fn install_shutdown() {
+    unsafe {
+        signal(SIGINT, handle_shutdown);
+        signal(SIGTERM, handle_shutdown);
+    }
+}
+
+pub fn install_swap_roles() {
+    unsafe {
+        signal(SIGUSR1, handle_swap_roles);
+    }
+}
+
+/// Blocks until ctrl-c or SIGTERM
+pub fn wait_for_shutdown() {
+    while !INTERRUPTED.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Whether SIGUSR1 asked to swap roles since the last call
+pub fn take_role_swap() -> bool {
+    SWAP_ROLES.swap(false, Ordering::SeqCst)
+}
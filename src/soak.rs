@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use cgmath::{vec2, vec3, Vector2, MetricSpace};
+
+use crash_report::{CrashRecorder, PipelineEvent};
+use decimation::Decimator;
+use inputs::{Input, InputPool};
+use pipeline::{Pipeline, HeadPose};
+use profile::Profile;
+use synthetic_input::SimulatedUser;
+use secs;
+
+const DT: f32 = 1.0 / 120.0; // seconds, about the rate of a TrackIR
+const TICKS_PER_HOUR: u64 = 120 * 60 * 60;
+const ACQUIRED_DIST: f32 = 30.0; // pixels from the target that count as reaching it
+const MAX_THROW_TIME: f32 = 2.0; // seconds, longer throws are stuck
+const MAX_STILL_TIME: f32 = 30.0; // seconds the cursor may sit still while targets change
+const STALL_INTERVAL: u64 = 120 * 60; // ticks between simulated stalls of the fusion loop
+const STALL_TIME: f64 = 0.25; // seconds each stall holds up the fusion loop
+const WORK_TIME: f64 = 0.0005; // seconds the fusion loop takes per input otherwise
+const RECORDER_WINDOW: u64 = 10; // seconds, like the app's crash recorder
+
+/// Per simulated hour statistics, to spot behaviour drifting over time
+struct HourStats {
+    targets: u32,
+    acquired: u32,
+    total_error: f32,
+    throws: u32,
+    max_remainder: f32,
+}
+
+impl HourStats {
+    fn new() -> Self {
+        HourStats {
+            targets: 0,
+            acquired: 0,
+            total_error: 0.0,
+            throws: 0,
+            max_remainder: 0.0,
+        }
+    }
+}
+
+struct Violations {
+    counts: BTreeMap<String, u64>,
+}
+
+impl Violations {
+    fn add(&mut self, time: f32, msg: String) {
+        let count = self.counts.entry(msg.clone()).or_insert(0);
+        if *count == 0 {
+            println!("[{:.1}s] invariant violated: {}", time, msg);
+        }
+        *count += 1;
+    }
+}
+
+/// Runs the pipeline against a simulated user for `hours` of simulated time as
+/// fast as possible, checking invariants every tick. Returns whether it passed.
+pub fn run(profile: Profile, hours: f32, seed: u32) -> bool {
+    println!("Soaking profile {} for {} simulated hours", profile.name, hours);
+    let display = vec2(1920.0, 1080.0);
+    // inputs go through a decimator and the crash recorder like in the app
+    let mut decimator = Decimator::new(profile.decimation.clone());
+    let shared_recorder = CrashRecorder::new(Duration::from_secs(RECORDER_WINDOW), &profile);
+    let mut recorder = shared_recorder.lock().unwrap();
+    let mut pipeline = Pipeline::new(profile);
+    let mut user = SimulatedUser::new(seed);
+    let mut cursor: Vector2<i32> = vec2(960, 540);
+
+    let mut violations = Violations { counts: BTreeMap::new() };
+    let mut stats = HourStats::new();
+    let start_head = user.head();
+    let mut target = user.target();
+    let mut throw_time = 0.0;
+    let mut still_time = 0.0;
+    let mut targets_while_still = 0;
+
+    let (tx, rx) = mpsc::sync_channel(InputPool::BUFFER);
+    let sim_start = Instant::now();
+    let at = |secs: f64| sim_start + Duration::new(secs as u64, (secs.fract() * 1.0e9) as u32);
+    let dropped = |decimator: &Decimator| decimator.stats.head_dropped + decimator.stats.gaze_dropped;
+    let mut queued = 0; // inputs sent but not handled or dropped yet
+    let mut loop_time = 0.0; // when the fusion loop is done with its last input
+    let mut last_head_time = 0.0;
+    let mut last_gaze_time = 0.0;
+
+    let ticks = (hours * TICKS_PER_HOUR as f32) as u64;
+    let wall_start = Instant::now();
+    for tick in 0..ticks {
+        let tick_time = tick as f64 * DT as f64;
+        let time = tick_time as f32;
+        let cursor_f = vec2(cursor.x as f32, cursor.y as f32);
+        let sample = user.step(DT, cursor_f, display);
+        // the pipeline expects the head pose inverted like LinuxTrack's
+        let head = sample.head * -1.0;
+        let inputs = vec![Input::TobiiGaze {
+                              x: sample.gaze.x,
+                              y: sample.gaze.y,
+                          },
+                          Input::LinuxTrackHead {
+                              yaw: head.x,
+                              pitch: head.y,
+                              x: 0.0,
+                              y: 0.0,
+                              z: 0.0,
+                          }];
+        for input in inputs {
+            // a full buffer blocks the trackers, losing the sample
+            if tx.try_send(input).is_ok() {
+                queued += 1;
+            }
+        }
+        if tick % STALL_INTERVAL == STALL_INTERVAL - 1 {
+            loop_time += STALL_TIME;
+        }
+
+        while queued > 0 && loop_time < tick_time + DT as f64 {
+            let done_at = loop_time;
+            loop_time = loop_time.max(tick_time);
+            let dropped_before = dropped(&decimator);
+            let input = decimator.next_at(&rx, at(done_at), at(loop_time));
+            queued -= 1 + dropped(&decimator) - dropped_before;
+            let received = loop_time;
+            loop_time += WORK_TIME;
+
+            let (yaw, pitch) = match input {
+                Input::TobiiGaze { x, y } => {
+                    let dt = (received - last_gaze_time) as f32;
+                    last_gaze_time = received;
+                    pipeline.gaze(vec2(x, y), dt, display);
+                    recorder.record_at(at(received),
+                                       PipelineEvent::Gaze {
+                                           dt,
+                                           gaze_pt: pipeline.px_gaze,
+                                           fixation_offset: pipeline.gaze_pt - pipeline.px_gaze,
+                                       });
+                    continue;
+                }
+                Input::LinuxTrackHead { yaw, pitch, .. } => (yaw, pitch),
+                _ => continue,
+            };
+            let dt = (received - last_head_time) as f32;
+            last_head_time = received;
+            let pose = HeadPose {
+                rotation: vec2(yaw, pitch),
+                translation: vec3(0.0, 0.0, 0.0),
+            };
+            let update = pipeline.head(pose, dt, cursor, display);
+            recorder.record_at(at(received),
+                               PipelineEvent::Head {
+                                   dt,
+                                   head_delta: update.head_delta,
+                                   cursor_move: update.cursor_move,
+                                   mouse_pt: cursor,
+                                   dest: update.dest,
+                                   jumped: update.jumped,
+                                   throwing: pipeline.poly_mouse.is_throwing(),
+                               });
+
+            if update.confined == cursor {
+                still_time += dt;
+            } else {
+                still_time = 0.0;
+                targets_while_still = 0;
+            }
+            cursor = update.confined;
+
+            if update.jumped {
+                stats.throws += 1;
+            }
+            if pipeline.poly_mouse.is_throwing() {
+                throw_time += dt;
+                if throw_time > MAX_THROW_TIME {
+                    violations.add(time, "stuck throwing".to_owned());
+                }
+            } else {
+                throw_time = 0.0;
+            }
+        }
+
+        if user.target() != target {
+            let target_px = vec2(target.x * display.x, target.y * display.y);
+            let error = target_px.distance(vec2(cursor.x as f32, cursor.y as f32));
+            stats.targets += 1;
+            stats.total_error += error;
+            if error < ACQUIRED_DIST {
+                stats.acquired += 1;
+            }
+            target = user.target();
+            if still_time > 0.0 {
+                targets_while_still += 1;
+            }
+        }
+        if still_time > MAX_STILL_TIME && targets_while_still > 1 {
+            violations.add(time, "cursor stuck while targets change".to_owned());
+        }
+
+        let remainder = pipeline.poly_mouse.rounding_remainder();
+        stats.max_remainder = stats.max_remainder.max(remainder.x.abs()).max(remainder.y.abs());
+        if let Err(msg) = pipeline.check_invariants() {
+            violations.add(time, msg);
+        }
+        if decimator.buffered() > Decimator::MAX_DRAIN {
+            violations.add(time, format!("decimator buffer grew to {}", decimator.buffered()));
+        }
+        // two inputs per tick, with room for one tick of jitter
+        let max_recorded = 2 * (RECORDER_WINDOW * 120 + 1) as usize;
+        if recorder.buffered() > max_recorded {
+            violations.add(time, format!("crash recorder history grew to {}", recorder.buffered()));
+        }
+
+        if (tick + 1) % TICKS_PER_HOUR == 0 || tick + 1 == ticks {
+            let head_drift = user.head() - start_head;
+            println!("hour {:.2}: {} targets, {:.1}% acquired, mean error {:.1}px, {} throws, \
+                      max rounding remainder {:.3}, head drift ({:.1}, {:.1})",
+                     (tick + 1) as f32 / TICKS_PER_HOUR as f32,
+                     stats.targets,
+                     100.0 * stats.acquired as f32 / stats.targets.max(1) as f32,
+                     stats.total_error / stats.targets.max(1) as f32,
+                     stats.throws,
+                     stats.max_remainder,
+                     head_drift.x,
+                     head_drift.y);
+            stats = HourStats::new();
+        }
+    }
+
+    let wall_secs = secs(wall_start.elapsed());
+    println!("Simulated {:.0}s in {:.1}s ({:.0}x real time)",
+             ticks as f32 * DT,
+             wall_secs,
+             ticks as f32 * DT / wall_secs.max(1.0e-3));
+    println!("Fell behind {} times, skipped {} head and {} gaze samples",
+             decimator.stats.overruns,
+             decimator.stats.head_dropped,
+             decimator.stats.gaze_dropped);
+
+    if violations.counts.is_empty() {
+        println!("All invariants held");
+        true
+    } else {
+        for (msg, count) in &violations.counts {
+            println!("{} x {}", count, msg);
+        }
+        false
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::mpsc::{SyncSender, Receiver};
+use std::time::{Duration, Instant};
+use std::thread;
+
+use cgmath::{Vector2, vec2, InnerSpace};
+use enigo::{Enigo, MouseControllable};
+
+use inputs::{Input, InputAction};
+use calc_dt;
+
+/// Small xorshift generator so simulations are reproducible from a seed
+pub struct XorShift {
+    state: u32,
+}
+
+impl XorShift {
+    pub fn new(seed: u32) -> Self {
+        XorShift { state: if seed == 0 { 0x9E37_79B9 } else { seed } }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// roughly gaussian with mean 0 and standard deviation 1
+    pub fn next_gaussian(&mut self) -> f32 {
+        let sum: f32 = (0..12).map(|_| self.next_f32()).sum();
+        sum - 6.0
+    }
+}
+
+pub struct SimulatedSample {
+    /// raw yaw and pitch like `Input::LinuxTrackHead`
+    pub head: Vector2<f32>,
+    /// normalized screen position like `Input::TobiiGaze`
+    pub gaze: Vector2<f32>,
+}
+
+/// A simulated user who looks at random targets and steers the cursor onto
+/// them with a quick head flick followed by slower corrective movements.
+/// The constants are roughly tuned against the default pipeline parameters.
+pub struct SimulatedUser {
+    rng: XorShift,
+    // f64 so small steps still add up after many simulated hours
+    time: f64,
+    target: Vector2<f32>,
+    last_target: Vector2<f32>,
+    target_time: f64,
+    head: Vector2<f32>,
+}
+
+impl SimulatedUser {
+    const TARGET_INTERVAL: f32 = 1.5; // seconds between new targets
+    const REACTION_TIME: f32 = 0.2; // seconds before eyes and head respond
+    const FLICK_TIME: f32 = 0.15; // seconds of fast head movement after a saccade
+    const MAX_HEAD_SPEED: f32 = 40.0; // head units per second
+    const CORRECTION_GAIN: f32 = 0.2; // head units per second per pixel of error
+    const GAZE_NOISE: f32 = 0.004; // normalized screen units
+    const HEAD_NOISE: f32 = 0.002; // head units
+
+    pub fn new(seed: u32) -> Self {
+        let center = vec2(0.5, 0.5);
+        SimulatedUser {
+            rng: XorShift::new(seed),
+            time: 0.0,
+            target: center,
+            last_target: center,
+            target_time: 0.0,
+            head: vec2(0.0, 0.0),
+        }
+    }
+
+    pub fn target(&self) -> Vector2<f32> {
+        self.target
+    }
+
+    pub fn head(&self) -> Vector2<f32> {
+        self.head
+    }
+
+    pub fn step(&mut self, dt: f32, cursor: Vector2<f32>, display: Vector2<f32>) -> SimulatedSample {
+        self.time += dt as f64;
+        if (self.time - self.target_time) as f32 > Self::TARGET_INTERVAL {
+            self.last_target = self.target;
+            self.target = vec2(0.1 + 0.8 * self.rng.next_f32(),
+                               0.1 + 0.8 * self.rng.next_f32());
+            self.target_time = self.time;
+        }
+        let since_target = (self.time - self.target_time) as f32;
+        let reacted = since_target >= Self::REACTION_TIME;
+
+        let looking_at = if reacted { self.target } else { self.last_target };
+        let gaze = looking_at +
+                   vec2(self.rng.next_gaussian(), self.rng.next_gaussian()) * Self::GAZE_NOISE;
+
+        if reacted {
+            let target_px = vec2(self.target.x * display.x, self.target.y * display.y);
+            let error = target_px - cursor;
+            let flicking = since_target < Self::REACTION_TIME + Self::FLICK_TIME;
+            let mut velocity = if flicking && error.magnitude() > 1.0 {
+                error.normalize() * Self::MAX_HEAD_SPEED
+            } else {
+                error * Self::CORRECTION_GAIN
+            };
+            if velocity.magnitude() > Self::MAX_HEAD_SPEED {
+                velocity = velocity.normalize() * Self::MAX_HEAD_SPEED;
+            }
+            // the pipeline inverts the head pose, so turn away from the error
+            self.head -= velocity * dt;
+        }
+        let head = self.head +
+                   vec2(self.rng.next_gaussian(), self.rng.next_gaussian()) * Self::HEAD_NOISE;
+
+        SimulatedSample { head, gaze }
+    }
+}
+
+pub fn listen(output: SyncSender<Input>, inbox: Receiver<InputAction>) {
+    let mut user = SimulatedUser::new(0x5EED);
+    let mut last_tick = Instant::now();
+    loop {
+        match inbox.try_recv() {
+            Ok(InputAction::Shutdown) => return,
+            Err(_) => (),
+        }
+
+        thread::sleep(Duration::from_millis(8)); // about the rate of a TrackIR
+        let dt = calc_dt(Instant::now(), &mut last_tick);
+
+        let (mouse_x, mouse_y) = Enigo::mouse_location();
+        let (display_width, display_height) = Enigo::main_display_size();
+        let sample = user.step(dt,
+                               vec2(mouse_x as f32, mouse_y as f32),
+                               vec2(display_width as f32, display_height as f32));
+
+        let head = Input::LinuxTrackHead {
+            yaw: sample.head.x,
+            pitch: sample.head.y,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let gaze = Input::TobiiGaze {
+            x: sample.gaze.x,
+            y: sample.gaze.y,
+        };
+        if output.send(gaze).is_err() || output.send(head).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_keep_changing_after_many_hours() {
+        let mut user = SimulatedUser::new(1);
+        user.time = 100.0 * 60.0 * 60.0;
+        user.target_time = user.time;
+        let first = user.target();
+        for _ in 0..(2 * 120) {
+            user.step(1.0 / 120.0, vec2(960.0, 540.0), vec2(1920.0, 1080.0));
+        }
+        assert!(user.target() != first);
+        assert!((user.time - 100.0 * 60.0 * 60.0 - 2.0).abs() < 1.0e-6);
+    }
+}
@@ -6,7 +6,7 @@ use std::os::raw;
 use std::sync::mpsc::{SyncSender, Receiver};
 use std::ffi::{CStr, CString};
 
-use inputs::{Input, InputAction};
+use inputs::{Input, InputAction, Tracker};
 
 use tobii_sys::helpers::{self, PtrWrapper, status_to_result, TobiiError};
 use signpost;
@@ -75,6 +75,7 @@ unsafe fn input_loop(output: SyncSender<Input>,
     status_to_result(status)?;
     let device = PtrWrapper::new(device_ptr, tobii_device_destroy);
 
+    let reconnected = output.clone();
     let mut context = Box::new(CallbackContext { output });
     let context_borrow = context.as_mut();
     let status = tobii_gaze_point_subscribe(device.ptr(),
@@ -95,6 +96,7 @@ unsafe fn input_loop(output: SyncSender<Input>,
             Err(TobiiError::TimedOut) => continue,
             Err(TobiiError::ConnectionFailed) => {
                 status_to_result(helpers::reconnect(device.ptr()))?;
+                reconnected.send(Input::Reconnected { tracker: Tracker::Gaze }).unwrap();
                 continue;
             }
             Err(e) => return Err(e),
@@ -104,6 +106,7 @@ unsafe fn input_loop(output: SyncSender<Input>,
         let status = tobii_process_callbacks(device.ptr());
         if status == TOBII_ERROR_CONNECTION_FAILED {
             status_to_result(helpers::reconnect(device.ptr()))?;
+            reconnected.send(Input::Reconnected { tracker: Tracker::Gaze }).unwrap();
             continue;
         }
         status_to_result(status)?;
@@ -0,0 +1,78 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use cgmath::Vector2;
+
+fn millis(dur: Duration) -> f64 {
+    dur.as_secs() as f64 * 1000.0 + dur.subsec_nanos() as f64 * 1.0e-6
+}
+
+fn wall_millis() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(millis).unwrap_or(0.0)
+}
+
+/// Logs cursor and gaze positions as CSV with wall clock timestamps in
+/// milliseconds since the unix epoch, the same clock screen recorders can stamp
+/// frames with (e.g. ffmpeg's `-use_wallclock_as_timestamps 1`), so the trail
+/// can be overlaid on a recording afterwards.
+///
+/// Sample times are derived from a monotonic clock so they never jump, and
+/// `marker` rows periodically pair it with the current wall clock so any drift
+/// between the two can be corrected when aligning.
+pub struct TrailLogger {
+    out: BufWriter<File>,
+    start: Instant,
+    start_wall: f64,
+    last_marker: Instant,
+}
+
+impl TrailLogger {
+    const MARKER_INTERVAL: u64 = 5; // seconds
+
+    pub fn create(path: &str) -> io::Result<TrailLogger> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "wall_ms,elapsed_ms,kind,x,y")?;
+        let mut logger = TrailLogger {
+            out,
+            start: Instant::now(),
+            start_wall: wall_millis(),
+            last_marker: Instant::now(),
+        };
+        logger.marker()?;
+        Ok(logger)
+    }
+
+    pub fn cursor(&mut self, pt: Vector2<i32>) {
+        self.log("cursor", pt.x as f32, pt.y as f32);
+    }
+
+    pub fn gaze(&mut self, pt: Vector2<f32>) {
+        self.log("gaze", pt.x, pt.y);
+    }
+
+    fn log(&mut self, kind: &str, x: f32, y: f32) {
+        let now = Instant::now();
+        let elapsed = millis(now.duration_since(self.start));
+        let mut res = writeln!(self.out,
+                               "{:.1},{:.1},{},{:.1},{:.1}",
+                               self.start_wall + elapsed,
+                               elapsed,
+                               kind,
+                               x,
+                               y);
+        if res.is_ok() && now.duration_since(self.last_marker).as_secs() >= Self::MARKER_INTERVAL {
+            res = self.marker();
+        }
+        if let Err(e) = res {
+            println!("Couldn't write to pointer trail: {}", e);
+        }
+    }
+
+    fn marker(&mut self) -> io::Result<()> {
+        self.last_marker = Instant::now();
+        let elapsed = millis(self.last_marker.duration_since(self.start));
+        writeln!(self.out, "{:.1},{:.1},marker,,", wall_millis(), elapsed)?;
+        self.out.flush()
+    }
+}
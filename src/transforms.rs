@@ -17,6 +17,10 @@ impl LowPassFilter {
         }
     }
 
+    pub fn reset(&mut self) {
+        self.first_time = true;
+    }
+
     pub fn filter(&mut self, x: f32, alpha: f32) -> f32 {
         if self.first_time {
             self.first_time = false;
@@ -49,6 +53,16 @@ impl OneEuroFilter {
         }
     }
 
+    pub fn reset(&mut self) {
+        self.first_time = true;
+        self.xfilt.reset();
+        self.dxfilt.reset();
+    }
+
+    pub fn set_mincutoff(&mut self, mincutoff: f32) {
+        self.mincutoff = mincutoff;
+    }
+
     pub fn filter(&mut self, x: f32, dt: f32) -> f32 {
         let rate = 1.0 / dt;
         let dx = if self.first_time {
@@ -83,13 +97,60 @@ impl VecOneEuroFilter {
         }
     }
 
+    pub fn reset(&mut self) {
+        self.xf.reset();
+        self.yf.reset();
+    }
+
+    pub fn set_mincutoff(&mut self, mincutoff: f32) {
+        self.xf.set_mincutoff(mincutoff);
+        self.yf.set_mincutoff(mincutoff);
+    }
+
     pub fn filter(&mut self, x: Vector2<f32>, dt: f32) -> Vector2<f32> {
         vec2(self.xf.filter(x.x, dt), self.yf.filter(x.y, dt))
     }
 }
 
+/// Ramps from 0 to 1 after a reset so filters can settle before they get full
+/// gain. Waits for both a minimum time and a minimum number of samples so slow
+/// trackers still get enough samples to stabilize.
+pub struct WarmUp {
+    min_time: f32,
+    min_samples: u32,
+    time: f32,
+    samples: u32,
+}
+
+impl WarmUp {
+    pub fn new(min_time: f32, min_samples: u32) -> Self {
+        WarmUp {
+            min_time,
+            min_samples,
+            time: 0.0,
+            samples: 0,
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+        self.samples = 0;
+    }
+
+    pub fn update(&mut self, dt: f32) -> f32 {
+        self.time += dt;
+        self.samples = self.samples.saturating_add(1);
+        let progress = (self.time / self.min_time)
+            .min(self.samples as f32 / self.min_samples as f32)
+            .min(1.0);
+        // smoothstep so the gain doesn't kick in abruptly at either end
+        progress * progress * (3.0 - 2.0 * progress)
+    }
+}
+
 /// Based on page 16 of Mathieu Nancel's "Mid-Air Pointing on Ultra-Walls" paper
 /// See the paper for how to set the constants.
+#[derive(Clone, Debug)]
 pub struct Acceleration {
     pub cd_min: f32,
     pub cd_max: f32,
@@ -109,6 +170,64 @@ impl Acceleration {
     }
 }
 
+/// Momentum for head-relative movement. A quick head flick launches the cursor
+/// with the flick's velocity, which then decays exponentially with `friction`
+/// so users with a limited range of motion can cover large distances.
+#[derive(Clone, Debug)]
+pub struct GlideParams {
+    pub flick_speed: f32,
+    pub friction: f32,
+    pub min_speed: f32,
+    /// speed against the glide that brakes it, above filtered tracker noise
+    pub brake_speed: f32,
+}
+
+pub struct Glide {
+    params: GlideParams,
+    velocity: Vector2<f32>,
+}
+
+impl Glide {
+    pub fn new(params: GlideParams) -> Self {
+        Glide {
+            params,
+            velocity: vec2(0.0, 0.0),
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.velocity = vec2(0.0, 0.0);
+    }
+
+    pub fn velocity(&self) -> Vector2<f32> {
+        self.velocity
+    }
+
+    pub fn transform(&mut self, delta: Vector2<f32>, dt: f32) -> Vector2<f32> {
+        if dt == 0.0 {
+            return delta;
+        }
+        let input_velocity = delta / dt;
+        if input_velocity.magnitude() > self.params.flick_speed {
+            self.velocity = input_velocity;
+            return delta;
+        }
+
+        // moving the head against the glide acts as a brake
+        let speed = self.velocity.magnitude();
+        if speed > 0.0 && -input_velocity.dot(self.velocity) / speed > self.params.brake_speed {
+            self.stop();
+            return delta;
+        }
+
+        self.velocity = self.velocity * f32::exp(-self.params.friction * dt);
+        if self.velocity.magnitude() < self.params.min_speed {
+            self.stop();
+        }
+        delta + self.velocity * dt
+    }
+}
+
 pub struct AccumulatingRounder {
     accum: f32,
 }
@@ -128,6 +247,66 @@ impl AccumulatingRounder {
         }
         res as i32
     }
+
+    pub fn remainder(&self) -> f32 {
+        self.accum
+    }
+}
+
+/// How fractional cursor coordinates are turned into pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    Truncate,
+    RoundHalfEven,
+    /// Carry the fractional remainder over to later moves, see `AccumulatingRounder`
+    Accumulate,
+}
+
+impl Rounding {
+    pub fn names() -> &'static [&'static str] {
+        &["truncate", "round-half-even", "accumulate"]
+    }
+
+    pub fn by_name(name: &str) -> Option<Rounding> {
+        match name {
+            "truncate" => Some(Rounding::Truncate),
+            "round-half-even" => Some(Rounding::RoundHalfEven),
+            "accumulate" => Some(Rounding::Accumulate),
+            _ => None,
+        }
+    }
+}
+
+pub struct Rounder {
+    strategy: Rounding,
+    accum: AccumulatingRounder,
+}
+
+impl Rounder {
+    pub fn new(strategy: Rounding) -> Self {
+        Rounder {
+            strategy,
+            accum: AccumulatingRounder::new(),
+        }
+    }
+
+    pub fn round(&mut self, x: f32) -> i32 {
+        match self.strategy {
+            Rounding::Truncate => x as i32,
+            Rounding::RoundHalfEven => {
+                if (x - x.trunc()).abs() == 0.5 {
+                    (2.0 * (x / 2.0).round()) as i32
+                } else {
+                    x.round() as i32
+                }
+            }
+            Rounding::Accumulate => self.accum.round(x),
+        }
+    }
+
+    pub fn remainder(&self) -> f32 {
+        self.accum.remainder()
+    }
 }
 
 pub struct FixationFilter {
@@ -138,7 +317,7 @@ pub struct FixationFilter {
 }
 
 impl FixationFilter {
-    const MAX_BUFFER: usize = 128;
+    pub const MAX_BUFFER: usize = 128;
 
     pub fn new(min_fixation_s: f32, max_velocity: f32) -> Self {
         FixationFilter {
@@ -149,6 +328,14 @@ impl FixationFilter {
         }
     }
 
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
     pub fn transform(&mut self, pt: Vector2<f32>, dt: f32) -> Vector2<f32> {
         if self.buffer.len() >= Self::MAX_BUFFER {
             self.buffer.pop_front();
@@ -194,7 +381,7 @@ impl FixationFilter {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct PolyMouseParams {
     pub min_jump: f32,
     pub speed_expand_factor: f32,
@@ -202,26 +389,30 @@ pub struct PolyMouseParams {
     pub throw_thresh_speed: f32,
     pub throw_speed: f32,
     pub small_jump_factor: f32,
+    pub rounding: Rounding,
 }
 
 pub struct PolyMouseTransform {
     params: PolyMouseParams,
     throwing: bool,
+    /// whether the last transform started a jump
+    jumped: bool,
     smoothed_head_speed: f32,
     pub last_jump_destination: Vector2<f32>,
-    x_round: AccumulatingRounder,
-    y_round: AccumulatingRounder,
+    x_round: Rounder,
+    y_round: Rounder,
 }
 
 impl PolyMouseTransform {
     pub fn new(params: PolyMouseParams) -> Self {
         PolyMouseTransform {
+            x_round: Rounder::new(params.rounding),
+            y_round: Rounder::new(params.rounding),
             params,
             throwing: false,
+            jumped: false,
             smoothed_head_speed: 0.0,
             last_jump_destination: vec2(0.0, 0.0),
-            x_round: AccumulatingRounder::new(),
-            y_round: AccumulatingRounder::new(),
         }
     }
 
@@ -231,18 +422,33 @@ impl PolyMouseTransform {
                      head_delta: Vector2<f32>,
                      dt: f32)
                      -> Vector2<i32> {
+        self.transform_split(gaze_pt, mouse_pt, head_delta, head_delta, dt)
+    }
+
+    /// Like `transform` but with the movement that triggers throws separate from
+    /// the fine movement, for when the jump target and fine movement come from
+    /// different inputs than usual.
+    pub fn transform_split(&mut self,
+                           gaze_pt: Vector2<f32>,
+                           mouse_pt: Vector2<i32>,
+                           head_delta: Vector2<f32>,
+                           trigger_delta: Vector2<f32>,
+                           dt: f32)
+                           -> Vector2<i32> {
         let mouse_pt_f = vec2(mouse_pt.x as f32, mouse_pt.y as f32);
 
         // TODO this is accelerated speed, should the acceleration be after?
-        let head_speed = head_delta.magnitude() / dt;
+        let head_speed = trigger_delta.magnitude() / dt;
         // TODO the amount of smoothing isn't independent of dt
         self.smoothed_head_speed = self.smoothed_head_speed *
                                    (1.0 - self.params.head_smoothing_factor) +
                                    head_speed * self.params.head_smoothing_factor;
 
         // println!("{:?}", self.smoothed_head_speed);
+        self.jumped = false;
         if self.looking_far_away(gaze_pt, mouse_pt_f) &&
            self.smoothed_head_speed > self.params.throw_thresh_speed {
+            self.jumped = !self.throwing;
             self.throwing = true;
         }
 
@@ -259,15 +465,46 @@ impl PolyMouseTransform {
                 gaze_pt + dirn * (-self.params.min_jump)
             };
 
-            vec2(dest_f.x as i32, dest_f.y as i32) // TODO round?
+            // round the move rather than the destination so the rounding
+            // strategy treats throws and head movement the same way
+            self.round_move(mouse_pt, dest_f - mouse_pt_f)
         } else {
-            let rounded_move = vec2(self.x_round.round(head_delta.x),
-                                    self.y_round.round(head_delta.y));
-            mouse_pt + rounded_move
+            self.round_move(mouse_pt, head_delta)
         }
     }
 
-    fn looking_far_away(&self, gaze_pt: Vector2<f32>, mouse_pt: Vector2<f32>) -> bool {
+    /// Moves `mouse_pt` by `delta` rounded with the profile's rounding strategy,
+    /// for anything else that moves the cursor to use the same rounding
+    pub fn round_move(&mut self, mouse_pt: Vector2<i32>, delta: Vector2<f32>) -> Vector2<i32> {
+        let rounded_move = vec2(self.x_round.round(delta.x), self.y_round.round(delta.y));
+        mouse_pt + rounded_move
+    }
+
+    pub fn reset(&mut self) {
+        self.throwing = false;
+        self.jumped = false;
+        self.smoothed_head_speed = 0.0;
+    }
+
+    pub fn is_throwing(&self) -> bool {
+        self.throwing
+    }
+
+    /// Whether the last transform started a jump. Short jumps start and land
+    /// in the same transform, so they never show in `is_throwing`.
+    pub fn jumped(&self) -> bool {
+        self.jumped
+    }
+
+    pub fn smoothed_head_speed(&self) -> f32 {
+        self.smoothed_head_speed
+    }
+
+    pub fn rounding_remainder(&self) -> Vector2<f32> {
+        vec2(self.x_round.remainder(), self.y_round.remainder())
+    }
+
+    pub fn looking_far_away(&self, gaze_pt: Vector2<f32>, mouse_pt: Vector2<f32>) -> bool {
         let jump_radius = self.params.min_jump +
                           self.smoothed_head_speed * self.params.speed_expand_factor;
         let small_jump = jump_radius * self.params.small_jump_factor;
@@ -275,3 +512,455 @@ impl PolyMouseTransform {
         self.last_jump_destination.distance(gaze_pt) > small_jump
     }
 }
+
+#[derive(Clone, Debug)]
+pub struct PuckParams {
+    pub scale: f32, // size of the mini-map as a fraction of the screen
+    pub open_time: f32, // seconds of looking away with a still head before the puck opens
+    pub close_time: f32, // seconds of a still head before the puck closes
+    pub still_speed: f32, // cursor pixels per second below which the head counts as still
+}
+
+/// A mini-map of the whole screen shown near the gaze point
+#[derive(Clone, Copy, Debug)]
+pub struct Puck {
+    /// top left corner of the mini-map in screen pixels
+    pub origin: Vector2<f32>,
+    pub size: Vector2<f32>,
+    /// position of the puck cursor within the mini-map, in screen pixels
+    pub pos: Vector2<f32>,
+}
+
+// the mini-map is only drawn by the debug overlay
+#[cfg(feature = "viz-2d")]
+impl Puck {
+    pub fn center(&self) -> Vector2<f32> {
+        self.origin + self.size / 2.0
+    }
+}
+
+/// Alternative large distance navigation for users who can't make fast
+/// saccades or head flicks. Looking away from the cursor while holding the
+/// head still opens a mini-map of the screen at the gaze point, within which
+/// fine head movement maps absolutely to the whole screen.
+pub struct PuckTransform {
+    params: PuckParams,
+    far_for: f32,
+    still_for: f32,
+    puck: Option<Puck>,
+}
+
+impl PuckTransform {
+    pub fn new(params: PuckParams) -> Self {
+        PuckTransform {
+            params,
+            far_for: 0.0,
+            still_for: 0.0,
+            puck: None,
+        }
+    }
+
+    #[cfg(feature = "viz-2d")]
+    pub fn puck(&self) -> Option<Puck> {
+        self.puck
+    }
+
+    pub fn reset(&mut self) {
+        self.far_for = 0.0;
+        self.still_for = 0.0;
+        self.puck = None;
+    }
+
+    /// Returns where the cursor should go while the puck is open, `None` when
+    /// it's closed and movement should be handled normally.
+    pub fn transform(&mut self,
+                     gaze_pt: Vector2<f32>,
+                     mouse_pt: Vector2<f32>,
+                     head_delta: Vector2<f32>,
+                     far_away: bool,
+                     dt: f32,
+                     display: Vector2<f32>)
+                     -> Option<Vector2<f32>> {
+        let still = dt > 0.0 && head_delta.magnitude() / dt < self.params.still_speed;
+        if still {
+            self.still_for += dt;
+        } else {
+            self.still_for = 0.0;
+        }
+
+        if let Some(mut puck) = self.puck {
+            if self.still_for >= self.params.close_time {
+                self.reset();
+                return None;
+            }
+            puck.pos += head_delta;
+            puck.pos.x = puck.pos.x.max(puck.origin.x).min(puck.origin.x + puck.size.x);
+            puck.pos.y = puck.pos.y.max(puck.origin.y).min(puck.origin.y + puck.size.y);
+            self.puck = Some(puck);
+            let rel = puck.pos - puck.origin;
+            return Some(vec2(rel.x / puck.size.x * display.x, rel.y / puck.size.y * display.y));
+        }
+
+        if far_away && still && gaze_pt.distance(mouse_pt) > 0.0 {
+            self.far_for += dt;
+        } else {
+            self.far_for = 0.0;
+        }
+        if self.far_for < self.params.open_time {
+            return None;
+        }
+
+        let size = display * self.params.scale;
+        let mut origin = gaze_pt - size / 2.0;
+        origin.x = origin.x.max(0.0).min(display.x - size.x);
+        origin.y = origin.y.max(0.0).min(display.y - size.y);
+        // start the puck cursor where the gaze is so opening acts like a jump
+        let pos = origin + vec2(gaze_pt.x / display.x * size.x, gaze_pt.y / display.y * size.y);
+        self.puck = Some(Puck { origin, size, pos });
+        self.far_for = 0.0;
+        self.still_for = 0.0;
+        Some(gaze_pt)
+    }
+}
+
+/// Offsets on a regular grid over normalized screen coordinates, bilinearly
+/// interpolated to correct distortions that don't fit a simple model.
+#[derive(Clone, Debug)]
+pub struct CorrectionMesh {
+    pub cols: usize,
+    pub rows: usize,
+    /// row-major, `offsets[row * cols + col]` is added at that grid node
+    pub offsets: Vec<Vector2<f32>>,
+}
+
+impl CorrectionMesh {
+    /// Fits node offsets to calibration samples of (measured gaze, true position)
+    /// using inverse distance weighting, so nodes are pulled by nearby samples.
+    pub fn fit(cols: usize, rows: usize, samples: &[(Vector2<f32>, Vector2<f32>)]) -> Self {
+        assert!(cols >= 2 && rows >= 2);
+        let mut offsets = Vec::with_capacity(cols * rows);
+        for row in 0..rows {
+            for col in 0..cols {
+                let node = Self::node_pos(cols, rows, col, row);
+                let mut total = vec2(0.0, 0.0);
+                let mut total_weight = 0.0;
+                for &(measured, actual) in samples {
+                    let weight = 1.0 / (node.distance2(measured) + 1.0e-4);
+                    total += (actual - measured) * weight;
+                    total_weight += weight;
+                }
+                offsets.push(if total_weight > 0.0 {
+                                 total / total_weight
+                             } else {
+                                 vec2(0.0, 0.0)
+                             });
+            }
+        }
+        CorrectionMesh { cols, rows, offsets }
+    }
+
+    fn node_pos(cols: usize, rows: usize, col: usize, row: usize) -> Vector2<f32> {
+        vec2(col as f32 / (cols - 1) as f32, row as f32 / (rows - 1) as f32)
+    }
+
+    pub fn offset_at(&self, pt: Vector2<f32>) -> Vector2<f32> {
+        let gx = (pt.x.max(0.0).min(1.0)) * (self.cols - 1) as f32;
+        let gy = (pt.y.max(0.0).min(1.0)) * (self.rows - 1) as f32;
+        let col = (gx.floor() as usize).min(self.cols - 2);
+        let row = (gy.floor() as usize).min(self.rows - 2);
+        let (fx, fy) = (gx - col as f32, gy - row as f32);
+
+        let at = |c: usize, r: usize| self.offsets[r * self.cols + c];
+        let top = at(col, row) * (1.0 - fx) + at(col + 1, row) * fx;
+        let bottom = at(col, row + 1) * (1.0 - fx) + at(col + 1, row + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GeometryParams {
+    /// where the screen corners show up in gaze coordinates, clockwise from the top left
+    pub keystone: Option<[Vector2<f32>; 4]>,
+    /// horizontal angle in radians covered by a curved screen, 0 for flat screens
+    pub curve_angle: f32,
+    pub mesh: Option<CorrectionMesh>,
+}
+
+/// Corrects normalized gaze coordinates for screens where the tracker's linear
+/// mapping is visibly wrong, like curved ultrawides and keystoned projectors.
+/// Applies the keystone homography, then the curvature, then the mesh.
+pub struct GeometryCorrection {
+    homography: Option<[f32; 9]>,
+    curve_angle: f32,
+    mesh: Option<CorrectionMesh>,
+}
+
+impl GeometryCorrection {
+    pub fn new(params: GeometryParams) -> Self {
+        let homography = params.keystone.and_then(|corners| {
+            let h = Self::fit_homography(&corners);
+            if h.is_none() {
+                println!("Warning: keystone corners are degenerate, ignoring them");
+            }
+            h
+        });
+        GeometryCorrection {
+            homography,
+            curve_angle: params.curve_angle,
+            mesh: params.mesh,
+        }
+    }
+
+    /// Fits a mesh for `params` to calibration samples of (measured gaze, true
+    /// position). The mesh is applied after the keystone and curve corrections,
+    /// so it's fitted to what's left over once those have been applied.
+    pub fn fit_mesh(params: &GeometryParams,
+                    cols: usize,
+                    rows: usize,
+                    samples: &[(Vector2<f32>, Vector2<f32>)])
+                    -> CorrectionMesh {
+        let base = GeometryCorrection::new(GeometryParams {
+                                               mesh: None,
+                                               ..params.clone()
+                                           });
+        let corrected: Vec<(Vector2<f32>, Vector2<f32>)> = samples
+            .iter()
+            .map(|&(measured, actual)| (base.transform(measured), actual))
+            .collect();
+        CorrectionMesh::fit(cols, rows, &corrected)
+    }
+
+    /// Solves for the homography taking `corners` to the corners of the unit square
+    fn fit_homography(corners: &[Vector2<f32>; 4]) -> Option<[f32; 9]> {
+        let targets = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let mut m = [[0.0f64; 9]; 8];
+        for i in 0..4 {
+            let (x, y) = (corners[i].x as f64, corners[i].y as f64);
+            let (u, v) = targets[i];
+            m[2 * i] = [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, u];
+            m[2 * i + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, v];
+        }
+
+        // gaussian elimination with partial pivoting
+        for col in 0..8 {
+            let pivot = (col..8)
+                .max_by(|&a, &b| m[a][col].abs().partial_cmp(&m[b][col].abs()).unwrap())
+                .unwrap();
+            if m[pivot][col].abs() < 1.0e-9 {
+                return None;
+            }
+            m.swap(col, pivot);
+            for row in 0..8 {
+                if row != col {
+                    let factor = m[row][col] / m[col][col];
+                    for k in col..9 {
+                        m[row][k] -= factor * m[col][k];
+                    }
+                }
+            }
+        }
+
+        let mut h = [1.0f32; 9];
+        for i in 0..8 {
+            h[i] = (m[i][8] / m[i][i]) as f32;
+        }
+        Some(h)
+    }
+
+    pub fn transform(&self, pt: Vector2<f32>) -> Vector2<f32> {
+        let mut pt = pt;
+        if let Some(h) = self.homography {
+            let w = h[6] * pt.x + h[7] * pt.y + h[8];
+            pt = vec2((h[0] * pt.x + h[1] * pt.y + h[2]) / w,
+                      (h[3] * pt.x + h[4] * pt.y + h[5]) / w);
+        }
+        if self.curve_angle > 0.0 {
+            // the tracker projects onto the flat chord of the screen, but pixels
+            // are spaced evenly along the arc so map through the viewing angle
+            let half = self.curve_angle / 2.0;
+            let angle = ((pt.x * 2.0 - 1.0) * half.tan()).atan();
+            pt.x = 0.5 + angle / self.curve_angle;
+        }
+        if let Some(ref mesh) = self.mesh {
+            pt += mesh.offset_at(pt);
+        }
+        pt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use profile::Profile;
+
+    #[test]
+    fn short_jumps_are_reported() {
+        let mut poly_mouse = PolyMouseTransform::new(Profile::default().polymouse);
+        let dt = 1.0 / 120.0;
+        // close enough to land within a single tick of throwing
+        let dest = poly_mouse.transform(vec2(1110.0, 540.0), vec2(960, 540), vec2(20.0, 0.0), dt);
+        assert!(poly_mouse.jumped());
+        assert!(!poly_mouse.is_throwing());
+        assert_eq!(dest, vec2(1010, 540));
+
+        poly_mouse.transform(vec2(1110.0, 540.0), dest, vec2(0.0, 0.0), dt);
+        assert!(!poly_mouse.jumped());
+    }
+
+    #[test]
+    fn long_jumps_are_reported_once() {
+        let mut poly_mouse = PolyMouseTransform::new(Profile::default().polymouse);
+        let dt = 1.0 / 120.0;
+        let mut mouse_pt = vec2(100, 540);
+        let mut jumps = 0;
+        for _ in 0..30 {
+            mouse_pt = poly_mouse.transform(vec2(1800.0, 540.0), mouse_pt, vec2(20.0, 0.0), dt);
+            if poly_mouse.jumped() {
+                jumps += 1;
+            }
+        }
+        assert_eq!(jumps, 1);
+        assert_eq!(mouse_pt.y, 540);
+    }
+
+    fn glide() -> Glide {
+        Glide::new(GlideParams {
+            flick_speed: 2500.0,
+            friction: 4.0,
+            min_speed: 40.0,
+            brake_speed: 200.0,
+        })
+    }
+
+    #[test]
+    fn flicks_launch_a_glide() {
+        let mut glide = glide();
+        let dt = 0.01;
+        assert_eq!(glide.transform(vec2(10.0, 0.0), dt), vec2(10.0, 0.0));
+        assert_eq!(glide.velocity(), vec2(0.0, 0.0));
+
+        assert_eq!(glide.transform(vec2(30.0, 0.0), dt), vec2(30.0, 0.0));
+        assert_eq!(glide.velocity(), vec2(3000.0, 0.0));
+        let coasting = glide.transform(vec2(0.0, 0.0), dt);
+        assert!(coasting.x > 25.0 && coasting.x < 30.0);
+        assert_eq!(coasting.y, 0.0);
+    }
+
+    #[test]
+    fn glides_decay_to_a_stop() {
+        let mut glide = glide();
+        let dt = 0.01;
+        glide.transform(vec2(30.0, 0.0), dt);
+        let mut last = glide.velocity().x;
+        for _ in 0..200 {
+            glide.transform(vec2(0.0, 0.0), dt);
+            assert!(glide.velocity().x < last || glide.velocity().x == 0.0);
+            last = glide.velocity().x;
+        }
+        assert_eq!(glide.velocity(), vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn only_deliberate_movement_brakes() {
+        let mut glide = glide();
+        let dt = 0.01;
+        glide.transform(vec2(30.0, 0.0), dt);
+        // noise against the glide is below the brake speed
+        glide.transform(vec2(-1.0, 0.5), dt);
+        assert!(glide.velocity().x > 2000.0);
+        // so is moving sideways
+        glide.transform(vec2(0.0, 10.0), dt);
+        assert!(glide.velocity().x > 2000.0);
+        assert_eq!(glide.transform(vec2(-5.0, 0.0), dt), vec2(-5.0, 0.0));
+        assert_eq!(glide.velocity(), vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn round_half_even_ties_go_to_even() {
+        let mut rounder = Rounder::new(Rounding::RoundHalfEven);
+        let rounded: Vec<i32> = [0.5, 1.5, 2.5, -0.5, -1.5, -2.5, 2.4, -2.6]
+            .iter()
+            .map(|&x| rounder.round(x))
+            .collect();
+        assert_eq!(rounded, vec![0, 2, 2, 0, -2, -2, 2, -3]);
+    }
+
+    #[test]
+    fn truncate_rounds_towards_zero() {
+        let mut rounder = Rounder::new(Rounding::Truncate);
+        assert_eq!(rounder.round(1.9), 1);
+        assert_eq!(rounder.round(-1.9), -1);
+    }
+
+    #[test]
+    fn accumulate_carries_remainders() {
+        let mut rounder = Rounder::new(Rounding::Accumulate);
+        let total: i32 = (0..10).map(|_| rounder.round(0.25)).sum();
+        assert_eq!(total, 2);
+        assert_eq!(rounder.remainder(), 0.5);
+
+        let total: i32 = (0..10).map(|_| rounder.round(-0.25)).sum();
+        assert_eq!(total, -2);
+        assert_eq!(rounder.remainder(), 0.0);
+    }
+
+    fn assert_near(a: Vector2<f32>, b: Vector2<f32>) {
+        assert!(a.distance(b) < 1.0e-3, "{:?} is not near {:?}", a, b);
+    }
+
+    #[test]
+    fn homography_maps_corners_to_unit_square() {
+        let corners = [vec2(0.1, 0.2), vec2(0.8, 0.1), vec2(0.9, 0.9), vec2(0.2, 0.8)];
+        let correction = GeometryCorrection::new(GeometryParams {
+                                                     keystone: Some(corners),
+                                                     curve_angle: 0.0,
+                                                     mesh: None,
+                                                 });
+        let targets = [vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0)];
+        for i in 0..4 {
+            assert_near(correction.transform(corners[i]), targets[i]);
+        }
+    }
+
+    #[test]
+    fn degenerate_corners_have_no_homography() {
+        let collinear = [vec2(0.0, 0.0), vec2(0.5, 0.5), vec2(1.0, 1.0), vec2(0.25, 0.25)];
+        assert!(GeometryCorrection::fit_homography(&collinear).is_none());
+        let repeated = [vec2(0.0, 0.0), vec2(0.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0)];
+        assert!(GeometryCorrection::fit_homography(&repeated).is_none());
+    }
+
+    #[test]
+    fn mesh_fits_a_uniform_offset() {
+        let samples: Vec<(Vector2<f32>, Vector2<f32>)> = [vec2(0.2, 0.3), vec2(0.7, 0.6), vec2(0.5, 0.9)]
+            .iter()
+            .map(|&pt| (pt, pt + vec2(0.05, -0.02)))
+            .collect();
+        let mesh = CorrectionMesh::fit(4, 3, &samples);
+        assert_near(mesh.offset_at(vec2(0.0, 0.0)), vec2(0.05, -0.02));
+        assert_near(mesh.offset_at(vec2(0.4, 0.55)), vec2(0.05, -0.02));
+    }
+
+    #[test]
+    fn mesh_is_fitted_after_keystone() {
+        // the projector image covers the middle of the tracker's range, and the
+        // tracker reads a little to the right everywhere
+        let params = GeometryParams {
+            keystone: Some([vec2(0.1, 0.1), vec2(0.9, 0.1), vec2(0.9, 0.9), vec2(0.1, 0.9)]),
+            curve_angle: 0.0,
+            mesh: None,
+        };
+        let measure = |pt: Vector2<f32>| vec2(0.1, 0.1) + pt * 0.8 + vec2(0.02, 0.0);
+        let samples: Vec<(Vector2<f32>, Vector2<f32>)> =
+            [vec2(0.1, 0.1), vec2(0.9, 0.2), vec2(0.5, 0.5), vec2(0.3, 0.8)]
+                .iter()
+                .map(|&pt| (measure(pt), pt))
+                .collect();
+        let mesh = GeometryCorrection::fit_mesh(&params, 4, 3, &samples);
+        let correction = GeometryCorrection::new(GeometryParams { mesh: Some(mesh), ..params });
+        for &(measured, actual) in &samples {
+            assert_near(correction.transform(measured), actual);
+        }
+        assert_near(correction.transform(measure(vec2(0.6, 0.4))), vec2(0.6, 0.4));
+    }
+}